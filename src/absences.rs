@@ -0,0 +1,116 @@
+use rusqlite::Connection;
+use anyhow::Result;
+use chrono::NaiveDate;
+use crate::OffKind;
+
+/// A whole or half day off. There is at most one absence per day.
+pub struct Absence {
+    pub day: NaiveDate,
+    pub kind: OffKind,
+    pub half: bool,
+    pub name: Option<String>,
+}
+
+impl Absence {
+    /// The share of the day's expected hours that is off
+    pub fn fraction(&self) -> f64 {
+        if self.half { 0.5 } else { 1.0 }
+    }
+
+    pub fn description(&self) -> String {
+        let mut text = match &self.name {
+            Some(name) => name.clone(),
+            None => kind_name(&self.kind).to_string(),
+        };
+        if self.half {
+            text.push_str(" (half day)");
+        }
+        text
+    }
+}
+
+pub fn kind_name(kind: &OffKind) -> &'static str {
+    match kind {
+        OffKind::Vacation => "vacation",
+        OffKind::Sick => "sick",
+        OffKind::Holiday => "holiday",
+        OffKind::Other => "other",
+    }
+}
+
+fn parse_kind(s: &str) -> Result<OffKind> {
+    match s {
+        "vacation" => Ok(OffKind::Vacation),
+        "sick" => Ok(OffKind::Sick),
+        "holiday" => Ok(OffKind::Holiday),
+        "other" => Ok(OffKind::Other),
+        _ => anyhow::bail!("Invalid absence kind '{}'", s),
+    }
+}
+
+/// Records an absence, replacing whatever was recorded for that day.
+/// Returns true if an existing entry was replaced.
+pub fn set_absence(conn: &Connection, absence: &Absence) -> Result<bool> {
+    let day = absence.day.format("%Y-%m-%d").to_string();
+    let tx = conn.unchecked_transaction()?;
+    let replaced = tx.execute("DELETE FROM absences WHERE day = ?1", [&day])? > 0;
+    tx.execute(
+        "INSERT INTO absences (day, kind, fraction, name) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![day, kind_name(&absence.kind), absence.fraction(), absence.name],
+    )?;
+    tx.commit()?;
+    Ok(replaced)
+}
+
+/// Records an absence unless the day already has one. Returns false if it
+/// was skipped.
+pub fn add_absence_if_free(conn: &Connection, absence: &Absence) -> Result<bool> {
+    let rows = conn.execute(
+        "INSERT INTO absences (day, kind, fraction, name) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(day) DO NOTHING",
+        rusqlite::params![
+            absence.day.format("%Y-%m-%d").to_string(),
+            kind_name(&absence.kind),
+            absence.fraction(),
+            absence.name
+        ],
+    )?;
+    Ok(rows > 0)
+}
+
+/// Absences from `from` up to and including `to`, in date order.
+pub fn get_absences(conn: &Connection, from: NaiveDate, to: NaiveDate) -> Result<Vec<Absence>> {
+    let mut stmt = conn.prepare(
+        "SELECT day, kind, fraction, name FROM absences
+         WHERE day BETWEEN ?1 AND ?2
+         ORDER BY day"
+    )?;
+
+    let rows = stmt.query_map(
+        [from.format("%Y-%m-%d").to_string(), to.format("%Y-%m-%d").to_string()],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, f64>(2)?, row.get::<_, Option<String>>(3)?)),
+    )?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        let (day, kind, fraction, name) = row?;
+        result.push(Absence {
+            day: NaiveDate::parse_from_str(&day, "%Y-%m-%d")?,
+            kind: parse_kind(&kind)?,
+            half: fraction < 1.0,
+            name,
+        });
+    }
+
+    Ok(result)
+}
+
+/// Removes the absences from `from` up to and including `to`. Returns how
+/// many there were.
+pub fn remove_absences(conn: &Connection, from: NaiveDate, to: NaiveDate) -> Result<usize> {
+    let rows = conn.execute(
+        "DELETE FROM absences WHERE day BETWEEN ?1 AND ?2",
+        [from.format("%Y-%m-%d").to_string(), to.format("%Y-%m-%d").to_string()],
+    )?;
+    Ok(rows)
+}
@@ -0,0 +1,132 @@
+use rusqlite::{Connection, OpenFlags, OptionalExtension, MAIN_DB};
+use anyhow::Result;
+use chrono::{DateTime, Local};
+use std::path::{Path, PathBuf};
+use crate::db;
+
+// Number of automatic snapshots kept before the oldest ones are removed
+const MAX_SNAPSHOTS: usize = 10;
+
+pub struct BackupFile {
+    pub path: PathBuf,
+    pub created: DateTime<Local>,
+    pub size: u64,
+    pub automatic: bool,
+}
+
+pub fn get_backup_dir() -> PathBuf {
+    let dir = db::get_data_dir().join("backups");
+    std::fs::create_dir_all(&dir).expect("Could not create backup directory");
+    dir
+}
+
+/// Copies the live database to `path` using SQLite's online backup API.
+pub fn backup_to(conn: &Connection, path: &Path) -> Result<()> {
+    if path.exists() {
+        anyhow::bail!("{} already exists", path.display());
+    }
+    conn.backup(MAIN_DB, path, None)?;
+    Ok(())
+}
+
+/// Takes an automatic safety snapshot before a destructive command and prunes
+/// old snapshots. Returns the path of the new snapshot.
+pub fn snapshot(conn: &Connection, reason: &str) -> Result<PathBuf> {
+    let timestamp = Local::now().format("%Y%m%d_%H%M%S");
+    let dir = get_backup_dir();
+
+    let mut path = dir.join(format!("auto_{}_{}.db", timestamp, reason));
+    let mut n = 1;
+    while path.exists() {
+        n += 1;
+        path = dir.join(format!("auto_{}_{}_{}.db", timestamp, reason, n));
+    }
+    backup_to(conn, &path)?;
+
+    let snapshots: Vec<_> = list_backups()?.into_iter().filter(|b| b.automatic).collect();
+    for old in snapshots.iter().skip(MAX_SNAPSHOTS) {
+        std::fs::remove_file(&old.path)?;
+    }
+
+    Ok(path)
+}
+
+/// Resolves a backup given either as a path or as a file name inside the
+/// backup directory.
+pub fn resolve(file: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(file);
+    if path.exists() {
+        return Ok(path);
+    }
+
+    let in_backup_dir = get_backup_dir().join(file);
+    if in_backup_dir.exists() {
+        return Ok(in_backup_dir);
+    }
+
+    anyhow::bail!("Backup '{}' not found", file)
+}
+
+/// Returns the schema version of a backup file, refusing files that are not
+/// walrus databases or that come from a newer walrus.
+pub fn check_backup(path: &Path) -> Result<i64> {
+    let src = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|_| anyhow::anyhow!("{} is not a SQLite database", path.display()))?;
+
+    let has_sessions = src.query_row(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'sessions'",
+        [],
+        |_| Ok(true),
+    ).optional()
+        .map_err(|_| anyhow::anyhow!("{} is not a SQLite database", path.display()))?
+        .unwrap_or(false);
+    if !has_sessions {
+        anyhow::bail!("{} is not a walrus database", path.display());
+    }
+
+    let version: i64 = src.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version > db::SCHEMA_VERSION {
+        anyhow::bail!(
+            "{} was created by a newer version of walrus (schema {}, this version supports {})",
+            path.display(), version, db::SCHEMA_VERSION
+        );
+    }
+
+    Ok(version)
+}
+
+/// Replaces the live database with the contents of `path` and upgrades it to
+/// the current schema.
+pub fn restore_from(conn: &mut Connection, path: &Path) -> Result<()> {
+    conn.restore(MAIN_DB, path, None::<fn(rusqlite::backup::Progress)>)?;
+    db::setup(conn)?;
+    Ok(())
+}
+
+/// Lists all backups, newest first.
+pub fn list_backups() -> Result<Vec<BackupFile>> {
+    let mut backups = Vec::new();
+
+    for entry in std::fs::read_dir(get_backup_dir())? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("db") {
+            continue;
+        }
+
+        let metadata = entry.metadata()?;
+        let automatic = path.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("auto_"));
+
+        backups.push(BackupFile {
+            path,
+            created: metadata.modified()?.into(),
+            size: metadata.len(),
+            automatic,
+        });
+    }
+
+    backups.sort_by(|a, b| b.created.cmp(&a.created).then_with(|| b.path.cmp(&a.path)));
+    Ok(backups)
+}
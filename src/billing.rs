@@ -0,0 +1,508 @@
+use rusqlite::{Connection, OptionalExtension};
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime};
+use crate::queries::{self, Session};
+use crate::topics;
+use crate::config::Config;
+use crate::rounding::{Rounding, RoundingMode, RoundingScope};
+use clap::ValueEnum;
+
+pub struct Client {
+    pub id: i64,
+    pub name: String,
+    pub currency: String,
+    /// Overrides the configured rounding
+    pub rounding: Option<Rounding>,
+}
+
+/// A project belongs to a client and claims all sessions whose topic matches
+/// its topic pattern.
+pub struct Project {
+    pub id: i64,
+    pub client_id: i64,
+    pub client: String,
+    pub name: String,
+    pub topic: String,
+    pub billable: bool,
+}
+
+/// An hourly rate for a whole client, narrowed to a project and/or topic
+/// pattern. Rates apply from `effective_from` (or always, if unset) until a
+/// newer rate for the same scope takes over.
+pub struct Rate {
+    pub id: i64,
+    pub client_id: i64,
+    pub client: String,
+    pub project_id: Option<i64>,
+    pub project: Option<String>,
+    pub topic: Option<String>,
+    pub rate: f64,
+    pub effective_from: Option<NaiveDate>,
+}
+
+pub struct BillingLine {
+    pub project: String,
+    pub topic: String,
+    pub hours: f64,
+    /// Hours after rounding, these are billed
+    pub rounded: f64,
+    pub rate: Option<f64>,
+    pub amount: f64,
+    pub billable: bool,
+}
+
+pub struct ClientBill {
+    pub client: String,
+    pub currency: String,
+    pub rounding: Rounding,
+    pub lines: Vec<BillingLine>,
+}
+
+impl ClientBill {
+    pub fn hours(&self) -> f64 {
+        self.lines.iter().map(|l| l.hours).sum()
+    }
+
+    pub fn rounded_hours(&self) -> f64 {
+        self.lines.iter().map(|l| l.rounded).sum()
+    }
+
+    pub fn billable_hours(&self) -> f64 {
+        self.lines.iter().filter(|l| l.billable).map(|l| l.hours).sum()
+    }
+
+    pub fn total(&self) -> f64 {
+        self.lines.iter().map(|l| l.amount).sum()
+    }
+}
+
+pub struct BillingReport {
+    pub clients: Vec<ClientBill>,
+    /// Hours per topic that no project claims
+    pub unassigned: Vec<(String, f64)>,
+}
+
+pub fn add_client(conn: &Connection, name: &str, currency: &str) -> Result<()> {
+    if find_client(conn, name)?.is_some() {
+        anyhow::bail!("Client '{}' already exists", name);
+    }
+    conn.execute(
+        "INSERT INTO clients (name, currency) VALUES (?1, ?2)",
+        [name, &currency.to_uppercase()],
+    )?;
+    Ok(())
+}
+
+const CLIENT_COLUMNS: &str = "id, name, currency, rounding_mode, rounding_increment, rounding_per";
+
+fn client_from_row(row: &rusqlite::Row) -> rusqlite::Result<Client> {
+    let mode: Option<String> = row.get(3)?;
+    let increment: Option<u32> = row.get(4)?;
+    let per: Option<String> = row.get(5)?;
+
+    let rounding = match (mode, increment, per) {
+        (Some(mode), Some(increment), Some(per)) => Some(Rounding {
+            mode: RoundingMode::from_str(&mode, true).unwrap_or(RoundingMode::Nearest),
+            increment,
+            per: RoundingScope::from_str(&per, true).unwrap_or(RoundingScope::Session),
+        }),
+        _ => None,
+    };
+
+    Ok(Client { id: row.get(0)?, name: row.get(1)?, currency: row.get(2)?, rounding })
+}
+
+pub fn get_clients(conn: &Connection) -> Result<Vec<Client>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM clients ORDER BY name", CLIENT_COLUMNS))?;
+    let clients = stmt.query_map([], client_from_row)?;
+
+    let result: Result<Vec<Client>, _> = clients.collect();
+    result.map_err(Into::into)
+}
+
+pub fn find_client(conn: &Connection, name: &str) -> Result<Option<Client>> {
+    let client = conn.query_row(
+        &format!("SELECT {} FROM clients WHERE name = ?1 COLLATE NOCASE", CLIENT_COLUMNS),
+        [name],
+        client_from_row,
+    ).optional()?;
+    Ok(client)
+}
+
+pub fn get_client(conn: &Connection, name: &str) -> Result<Client> {
+    find_client(conn, name)?.ok_or_else(|| anyhow::anyhow!("Client '{}' not found", name))
+}
+
+/// Sets or clears (None) the client's own rounding policy.
+pub fn set_client_rounding(conn: &Connection, id: i64, rounding: Option<&Rounding>) -> Result<()> {
+    let name = |v: Option<clap::builder::PossibleValue>| v.map(|v| v.get_name().to_string());
+    conn.execute(
+        "UPDATE clients SET rounding_mode = ?1, rounding_increment = ?2, rounding_per = ?3 WHERE id = ?4",
+        rusqlite::params![
+            rounding.and_then(|r| name(r.mode.to_possible_value())),
+            rounding.map(|r| r.increment),
+            rounding.and_then(|r| name(r.per.to_possible_value())),
+            id
+        ],
+    )?;
+    Ok(())
+}
+
+/// Removes a client together with its projects and rates.
+pub fn remove_client(conn: &Connection, id: i64) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM rates WHERE client_id = ?1", [id])?;
+    tx.execute("DELETE FROM projects WHERE client_id = ?1", [id])?;
+    tx.execute("DELETE FROM clients WHERE id = ?1", [id])?;
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn add_project(conn: &Connection, client_id: i64, name: &str, topic: &str, billable: bool) -> Result<()> {
+    if find_project(conn, name)?.is_some() {
+        anyhow::bail!("Project '{}' already exists", name);
+    }
+    conn.execute(
+        "INSERT INTO projects (client_id, name, topic, billable) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![client_id, name, topic, billable],
+    )?;
+    Ok(())
+}
+
+const PROJECT_COLUMNS: &str =
+    "p.id, p.client_id, c.name, p.name, p.topic, p.billable FROM projects p JOIN clients c ON c.id = p.client_id";
+
+fn project_from_row(row: &rusqlite::Row) -> rusqlite::Result<Project> {
+    Ok(Project {
+        id: row.get(0)?,
+        client_id: row.get(1)?,
+        client: row.get(2)?,
+        name: row.get(3)?,
+        topic: row.get(4)?,
+        billable: row.get(5)?,
+    })
+}
+
+pub fn get_projects(conn: &Connection) -> Result<Vec<Project>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} ORDER BY c.name, p.name", PROJECT_COLUMNS))?;
+    let projects = stmt.query_map([], project_from_row)?;
+
+    let result: Result<Vec<Project>, _> = projects.collect();
+    result.map_err(Into::into)
+}
+
+pub fn find_project(conn: &Connection, name: &str) -> Result<Option<Project>> {
+    let project = conn.query_row(
+        &format!("SELECT {} WHERE p.name = ?1 COLLATE NOCASE", PROJECT_COLUMNS),
+        [name],
+        project_from_row,
+    ).optional()?;
+    Ok(project)
+}
+
+pub fn get_project(conn: &Connection, name: &str) -> Result<Project> {
+    find_project(conn, name)?.ok_or_else(|| anyhow::anyhow!("Project '{}' not found", name))
+}
+
+pub fn update_project(conn: &Connection, id: i64, topic: Option<&str>, billable: Option<bool>) -> Result<()> {
+    if let Some(topic) = topic {
+        conn.execute("UPDATE projects SET topic = ?1 WHERE id = ?2", rusqlite::params![topic, id])?;
+    }
+    if let Some(billable) = billable {
+        conn.execute("UPDATE projects SET billable = ?1 WHERE id = ?2", rusqlite::params![billable, id])?;
+    }
+    Ok(())
+}
+
+/// Removes a project together with its rates.
+pub fn remove_project(conn: &Connection, id: i64) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM rates WHERE project_id = ?1", [id])?;
+    tx.execute("DELETE FROM projects WHERE id = ?1", [id])?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Sets the rate for a scope from a date on, replacing a rate for exactly the
+/// same scope and date.
+pub fn set_rate(
+    conn: &Connection,
+    client_id: i64,
+    project_id: Option<i64>,
+    topic: Option<&str>,
+    rate: f64,
+    effective_from: Option<NaiveDate>,
+) -> Result<()> {
+    let from = effective_from.map(|d| d.format("%Y-%m-%d").to_string());
+
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "DELETE FROM rates
+         WHERE client_id = ?1 AND project_id IS ?2 AND topic IS ?3 AND effective_from IS ?4",
+        rusqlite::params![client_id, project_id, topic, from],
+    )?;
+    tx.execute(
+        "INSERT INTO rates (client_id, project_id, topic, rate, effective_from) VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![client_id, project_id, topic, rate, from],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn get_rates(conn: &Connection) -> Result<Vec<Rate>> {
+    let mut stmt = conn.prepare(
+        "SELECT r.id, r.client_id, c.name, r.project_id, p.name, r.topic, r.rate, r.effective_from
+         FROM rates r
+         JOIN clients c ON c.id = r.client_id
+         LEFT JOIN projects p ON p.id = r.project_id
+         ORDER BY c.name, p.name, r.topic, r.effective_from"
+    )?;
+
+    let rates = stmt.query_map([], |row| {
+        let from: Option<String> = row.get(7)?;
+        Ok((
+            Rate {
+                id: row.get(0)?,
+                client_id: row.get(1)?,
+                client: row.get(2)?,
+                project_id: row.get(3)?,
+                project: row.get(4)?,
+                topic: row.get(5)?,
+                rate: row.get(6)?,
+                effective_from: None,
+            },
+            from,
+        ))
+    })?;
+
+    let mut result = Vec::new();
+    for rate in rates {
+        let (mut rate, from) = rate?;
+        rate.effective_from = from.map(|f| NaiveDate::parse_from_str(&f, "%Y-%m-%d")).transpose()?;
+        result.push(rate);
+    }
+
+    Ok(result)
+}
+
+pub fn remove_rate(conn: &Connection, id: i64) -> Result<bool> {
+    let rows = conn.execute("DELETE FROM rates WHERE id = ?1", [id])?;
+    Ok(rows > 0)
+}
+
+/// Everything needed to assign sessions to projects and price them.
+pub struct Pricing {
+    clients: Vec<Client>,
+    projects: Vec<Project>,
+    rates: Vec<Rate>,
+    separator: String,
+    rounding: Rounding,
+}
+
+impl Pricing {
+    pub fn load(conn: &Connection, config: &Config) -> Result<Pricing> {
+        Ok(Pricing {
+            clients: get_clients(conn)?,
+            projects: get_projects(conn)?,
+            rates: get_rates(conn)?,
+            separator: config.topics.separator.clone(),
+            rounding: config.rounding.clone(),
+        })
+    }
+
+    /// The client's own rounding policy, or the configured one.
+    pub fn rounding_for(&self, client_id: i64) -> &Rounding {
+        self.client(client_id)
+            .and_then(|c| c.rounding.as_ref())
+            .unwrap_or(&self.rounding)
+    }
+
+    /// The rounding policy of the client whose project claims the topic.
+    pub fn rounding_for_topic(&self, topic: &str) -> &Rounding {
+        match self.project_for(topic) {
+            Some(project) => self.rounding_for(project.client_id),
+            None => &self.rounding,
+        }
+    }
+
+    /// True if any time gets rounded at all.
+    pub fn rounds(&self) -> bool {
+        self.rounding.is_active() || self.clients.iter().any(|c| c.rounding.as_ref().is_some_and(|r| r.is_active()))
+    }
+
+    /// Rounds (topic, day, hours) entries, each topic by the policy of its client.
+    pub fn round_by_topic(&self, entries: &[(String, NaiveDate, f64)]) -> Vec<f64> {
+        let mut rounded = vec![0.0; entries.len()];
+        let mut done = vec![false; entries.len()];
+
+        for i in 0..entries.len() {
+            if done[i] {
+                continue;
+            }
+            let topic = &entries[i].0;
+            let indices: Vec<usize> = (i..entries.len()).filter(|&j| entries[j].0 == *topic).collect();
+            let subset: Vec<_> = indices.iter().map(|&j| entries[j].clone()).collect();
+            let values = self.rounding_for_topic(topic).apply(&subset);
+            for (&j, value) in indices.iter().zip(values) {
+                rounded[j] = value;
+                done[j] = true;
+            }
+        }
+
+        rounded
+    }
+
+    /// The project claiming a topic. If several match, the one with the most
+    /// specific (longest) topic pattern wins.
+    pub fn project_for(&self, topic: &str) -> Option<&Project> {
+        self.projects.iter()
+            .filter(|p| topics::matches(&p.topic, topic, &self.separator))
+            .max_by_key(|p| p.topic.len())
+    }
+
+    pub fn client(&self, id: i64) -> Option<&Client> {
+        self.clients.iter().find(|c| c.id == id)
+    }
+
+    /// The hourly rate for a session of `project` on `date`. Topic overrides
+    /// beat project rates, which beat the client's default rate; within a
+    /// scope the most recent effective rate applies.
+    pub fn rate_for(&self, project: &Project, topic: &str, date: NaiveDate) -> Option<f64> {
+        self.rates.iter()
+            .filter(|r| r.client_id == project.client_id)
+            .filter(|r| r.effective_from.is_none_or(|from| from <= date))
+            .filter_map(|r| {
+                if r.project_id.is_some_and(|id| id != project.id) {
+                    return None;
+                }
+                let specificity = match (&r.topic, r.project_id) {
+                    (Some(pattern), _) if topics::matches(pattern, topic, &self.separator) => 3,
+                    (Some(_), _) => return None,
+                    (None, Some(_)) => 2,
+                    (None, None) => 1,
+                };
+                Some(((specificity, r.effective_from), r.rate))
+            })
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, rate)| rate)
+    }
+}
+
+/// Prices all finished sessions started in `[start, end)`, grouped by client
+/// and then by project, topic and rate.
+pub fn billing_report(conn: &Connection, pricing: &Pricing, start: NaiveDateTime, end: NaiveDateTime) -> Result<BillingReport> {
+    let filter = queries::SessionFilter {
+        from: Some(queries::to_rfc3339(start)?),
+        to: Some(queries::to_rfc3339(end)?),
+        ..Default::default()
+    };
+    let sessions: Vec<(Session, f64)> = queries::get_matching_sessions(conn, &filter)?
+        .into_iter()
+        .filter(|(s, _)| s.end.is_some())
+        .collect();
+
+    let mut clients: Vec<ClientBill> = Vec::new();
+    let mut unassigned: Vec<(String, f64)> = Vec::new();
+    // (client, line, day, hours) of every priced session, for rounding
+    let mut entries: Vec<(usize, usize, NaiveDate, f64)> = Vec::new();
+
+    for (session, hours) in &sessions {
+        let hours = *hours;
+
+        let Some(project) = pricing.project_for(&session.topic) else {
+            match unassigned.iter_mut().find(|(t, _)| *t == session.topic) {
+                Some((_, h)) => *h += hours,
+                None => unassigned.push((session.topic.clone(), hours)),
+            }
+            continue;
+        };
+
+        let client = pricing.client(project.client_id)
+            .ok_or_else(|| anyhow::anyhow!("Project '{}' has no client", project.name))?;
+        let rate = if project.billable {
+            pricing.rate_for(project, &session.topic, session.start.date_naive())
+        } else {
+            None
+        };
+
+        let bill_index = match clients.iter().position(|c| c.client == client.name) {
+            Some(i) => i,
+            None => {
+                clients.push(ClientBill {
+                    client: client.name.clone(),
+                    currency: client.currency.clone(),
+                    rounding: pricing.rounding_for(client.id).clone(),
+                    lines: Vec::new(),
+                });
+                clients.len() - 1
+            }
+        };
+        let bill = &mut clients[bill_index];
+
+        let line_index = match bill.lines.iter()
+            .position(|l| l.project == project.name && l.topic == session.topic && l.rate == rate)
+        {
+            Some(i) => i,
+            None => {
+                bill.lines.push(BillingLine {
+                    project: project.name.clone(),
+                    topic: session.topic.clone(),
+                    hours: 0.0,
+                    rounded: 0.0,
+                    rate,
+                    amount: 0.0,
+                    billable: project.billable,
+                });
+                bill.lines.len() - 1
+            }
+        };
+        bill.lines[line_index].hours += hours;
+        entries.push((bill_index, line_index, session.start.date_naive(), hours));
+    }
+
+    for (bill_index, bill) in clients.iter_mut().enumerate() {
+        let bill_entries: Vec<(usize, NaiveDate, f64)> = entries.iter()
+            .filter(|e| e.0 == bill_index)
+            .map(|&(_, line, day, hours)| (line, day, hours))
+            .collect();
+        let rounded = bill.rounding.apply(&bill_entries);
+        for ((line, _, _), hours) in bill_entries.iter().zip(rounded) {
+            bill.lines[*line].rounded += hours;
+        }
+        for line in &mut bill.lines {
+            line.amount = line.rounded * line.rate.unwrap_or(0.0);
+        }
+    }
+
+    clients.sort_by(|a, b| a.client.cmp(&b.client));
+    for bill in &mut clients {
+        bill.lines.sort_by(|a, b| a.project.cmp(&b.project).then_with(|| a.topic.cmp(&b.topic)));
+    }
+    unassigned.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+
+    Ok(BillingReport { clients, unassigned })
+}
+
+/// Formats an amount like `CHF 1'234.50`.
+pub fn format_money(amount: f64, currency: &str) -> String {
+    let cents = (amount * 100.0).round() as i64;
+    let (whole, fraction) = (cents.abs() / 100, cents.abs() % 100);
+
+    let digits = whole.to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push('\'');
+        }
+        grouped.push(c);
+    }
+
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{} {}{}.{:02}", currency, sign, grouped, fraction)
+}
+
+/// Parses a month like `2025-10` into its first day.
+pub fn parse_month(s: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(&format!("{}-01", s), "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("Invalid month '{}'. Use YYYY-MM", s))
+}
@@ -1,17 +1,22 @@
 use rusqlite::Connection;
 use anyhow::Result;
 use chrono::{Local, NaiveDate, Duration, Datelike};
-use crate::{queries, display};
-use crate::Period;
+use crate::{db, queries, display, journal, backup, billing, invoice, periods, schedule, targets};
+use crate::{absences, holidays, pomodoro, settings, stale, timesheet};
+use crate::rounding::{RoundingMode, RoundingScope};
+use crate::{FilterArgs, InvoiceGrouping, OffKind, Period, ShowArgs, TimesheetFormat, TopicArgs};
+use crate::config::{self, Config};
 
-pub fn start(conn: &Connection, topic: Option<String>) -> Result<()> {
-    let topic_value = topic.as_deref().unwrap_or("default");
+pub fn start(conn: &Connection, topic: Option<String>, default_topic: &str) -> Result<()> {
+    let topic_value = topic.as_deref().unwrap_or(default_topic);
 
     if queries::get_active_session_for_topic(conn, topic_value)?.is_some() {
         anyhow::bail!("Session for '{}' is already active! Stop it first with 'walrus stop {}'", topic_value, topic_value);
     }
 
-    queries::start_session(conn, topic_value)?;
+    journal::transaction(conn, &format!("start {}", topic_value), |tx| {
+        queries::start_session(tx, topic_value)
+    })?;
 
     match topic {
         Some(t) => println!("Started: {}", t),
@@ -35,11 +40,13 @@ pub fn stop(conn: &Connection) -> Result<()> {
         anyhow::bail!("Please specify which session to stop using: walrus stop <topic>");
     } else {
         // Exactly one active session - stop it
-        let (id, _) = &active_sessions[0];
-        queries::stop_session(conn, *id)?;
+        let (id, topic) = &active_sessions[0];
+        journal::transaction(conn, &format!("stop {}", topic), |tx| {
+            queries::stop_session(tx, *id)
+        })?;
 
         println!("Stopped tracking");
-        let sessions = queries::get_sessions(conn, 1)?;
+        let sessions = queries::get_sessions(conn, 1, &queries::TopicFilter::default())?;
         display::print_sessions(&sessions, false);
 
         Ok(())
@@ -50,72 +57,602 @@ pub fn stop_topic(conn: &Connection, topic: &str) -> Result<()> {
     let active = queries::get_active_session_for_topic(conn, topic)?
         .ok_or_else(|| anyhow::anyhow!("No active session for '{}' to stop", topic))?;
 
-    queries::stop_session(conn, active.id)?;
+    journal::transaction(conn, &format!("stop {}", topic), |tx| {
+        queries::stop_session(tx, active.id)
+    })?;
 
     println!("Stopped tracking");
-    let sessions = queries::get_sessions(conn, 1)?;
+    let sessions = queries::get_sessions(conn, 1, &queries::TopicFilter::default())?;
     display::print_sessions(&sessions, false);
 
     Ok(())
 }
 
-pub fn show(conn: &Connection, count: usize, period: Option<Period>) -> Result<()> {
-    if let Some(active) = queries::get_active_session(conn)? {
-        display::print_active_session(&active);
+pub fn status(conn: &Connection, config: &Config, watch: bool) -> Result<()> {
+    use std::io::Write;
+
+    let targets = targets::get_targets(conn)?;
+    loop {
+        let now = Local::now();
+        let today = periods::range(Period::Day, 0, now.naive_local());
+        let active = queries::get_active_sessions(conn)?;
+        let total = queries::get_period_total(conn, today.start, today.end, now)?;
+
+        let mut progress = Vec::new();
+        for period in [Period::Day, Period::Week] {
+            let range = periods::range(period, 0, now.naive_local());
+            let totals = live_totals(conn, &range, &active, now)?;
+            progress.extend(targets::progress(&targets, &period, &totals, &config.topics.separator, true)
+                .into_iter()
+                .map(|p| targets::Progress { label: format!("{} ({})", p.label, targets::period_name(&period)), ..p }));
+        }
+
+        if watch {
+            // Redraw from the top left instead of scrolling
+            print!("\x1b[H\x1b[2J");
+        }
+        display::print_status(&active, total, &progress);
+        if !watch {
+            return Ok(());
+        }
+        std::io::stdout().flush()?;
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+/// Hours per topic in `range`, counting running sessions up to `now`.
+fn live_totals(
+    conn: &Connection,
+    range: &periods::Range,
+    active: &[queries::Session],
+    now: chrono::DateTime<Local>,
+) -> Result<Vec<(String, f64)>> {
+    let mut totals = queries::get_period_stats(conn, range.start, range.end, &queries::TopicFilter::default())?;
+    for session in active.iter().filter(|s| s.start.with_timezone(&Local).naive_local() >= range.start) {
+        let hours = now.signed_duration_since(session.start).num_seconds() as f64 / 3600.0;
+        match totals.iter_mut().find(|(topic, _)| *topic == session.topic) {
+            Some((_, sum)) => *sum += hours,
+            None => totals.push((session.topic.clone(), hours)),
+        }
+    }
+    Ok(totals)
+}
+
+pub fn completions(shell: clap_complete::Shell) -> Result<()> {
+    let name = shell.to_string();
+    let shells = clap_complete::env::Shells::builtins();
+    let completer = shells
+        .completer(&name)
+        .ok_or_else(|| anyhow::anyhow!("No completion support for {}", name))?;
+    completer.write_registration("COMPLETE", "walrus", "walrus", "walrus", &mut std::io::stdout())?;
+    Ok(())
+}
+
+/// Prints the value of `key`, or an empty line for an unset optional setting.
+pub fn config_get(key: &str) -> Result<()> {
+    let value = config::get(&config::load()?, key)?;
+    println!("{}", value.as_ref().map(config::value_text).unwrap_or_default());
+    Ok(())
+}
+
+pub fn config_set(key: &str, value: &str) -> Result<()> {
+    config::set(key, value)?;
+    let value = config::get(&config::load()?, key)?;
+    println!("Set {} = {}", key, value.as_ref().map(config::value_text).unwrap_or_default());
+    Ok(())
+}
+
+pub fn config_unset(key: &str) -> Result<()> {
+    if config::unset(key)? {
+        match config::get(&Config::default(), key)? {
+            Some(value) => println!("Reset {} to {}", key, config::value_text(&value)),
+            None => println!("Removed {}", key),
+        }
+    } else {
+        println!("{} is not set in the config file", key);
+    }
+    Ok(())
+}
+
+pub fn config_list() -> Result<()> {
+    let entries = config::list(&config::load()?)?;
+    display::print_config(&config::get_config_path(), &entries);
+    Ok(())
+}
+
+/// Prints the running sessions for a shell prompt or status bar. Prints an
+/// empty line when nothing runs.
+pub fn prompt(format: &str, separator: &str, waybar: bool) -> Result<()> {
+    let active = match db::open_read_only()? {
+        Some(conn) => queries::get_active_sessions(&conn)?,
+        None => Vec::new(),
+    };
+
+    let text = active.iter()
+        .map(|s| display::format_prompt(format, s))
+        .collect::<Vec<_>>()
+        .join(separator);
+
+    if waybar {
+        let tooltip = if active.is_empty() {
+            "No active session".to_string()
+        } else {
+            active.iter()
+                .map(|s| display::format_prompt("{topic} since {start} ({elapsed})", s))
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let class = if active.is_empty() { "idle" } else { "active" };
+        display::print_waybar(&text, &tooltip, class);
+    } else {
+        println!("{}", text);
+    }
+    Ok(())
+}
+
+pub fn show(conn: &Connection, config: &Config, args: &ShowArgs) -> Result<()> {
+    display::print_active_sessions(&queries::get_active_sessions(conn)?);
+
+    let now = Local::now().naive_local();
+    let topics = topic_filter(&args.topics);
+    let Some((period, ranges)) = show_ranges(args, now)? else {
+        let sessions = queries::get_sessions(conn, args.count, &topics)?;
+        display::print_sessions(&sessions, false);
+        return Ok(());
+    };
+
+    let targets = targets::get_targets(conn)?;
+    let pricing = billing::Pricing::load(conn, config)?;
+    let mut periods = Vec::new();
+    let span = ranges.iter().map(|r| r.start).min().zip(ranges.iter().map(|r| r.end).max());
+    let several = ranges.len() > 1;
+
+    for range in ranges {
+        let totals = queries::get_period_stats(conn, range.start, range.end, &topics)?;
+        let progress = targets::progress(&targets, &period, &totals, &config.topics.separator, topics.is_empty());
+        let absences = absences::get_absences(conn, range.start.date(), range.last_day)?;
+        let rounded_total = if pricing.rounds() {
+            let entries = queries::get_period_sessions(conn, range.start, range.end, &topics)?;
+            Some(pricing.round_by_topic(&entries).iter().fold(0.0, |sum, h| sum + h))
+        } else {
+            None
+        };
+        let pomodoros = queries::get_pomodoro_counts(conn, range.start, range.end, &topics)?;
+        periods.push(queries::PeriodStats { label: range.label, topics: totals, progress, absences, rounded_total, pomodoros });
     }
 
-    match period {
-        Some(Period::Day) => show_days(conn, count)?,
-        Some(Period::Week) => show_weeks(conn, count)?,
-        Some(Period::Month) => show_months(conn, count)?,
-        Some(Period::Year) => show_years(conn, count)?,
-        None => {
-            let sessions = queries::get_sessions(conn, count)?;
-            display::print_sessions(&sessions, false);
+    // Ranges are broken down by day, days with nothing to show are left out
+    if args.from.is_some() {
+        periods.retain(|p| !p.topics.is_empty() || !p.absences.is_empty());
+        if periods.is_empty() {
+            println!("Nothing tracked in this range");
+            return Ok(());
         }
     }
 
+    display::print_period_stats(&periods, &config.topics.separator, args.depth, args.chart);
+    if let Some((start, end)) = span.filter(|_| args.chart && several) {
+        display::print_sparkline(&daily_hours(conn, start, end, &topics)?);
+    }
     Ok(())
 }
 
-pub fn list(conn: &Connection, count: usize) -> Result<()> {
-    let sessions_with_hours = queries::get_sessions_with_calculated_hours(conn, count)?;
+/// Hours tracked on each day from `start` up to `end`, days without any included.
+fn daily_hours(
+    conn: &Connection,
+    start: chrono::NaiveDateTime,
+    end: chrono::NaiveDateTime,
+    topics: &queries::TopicFilter,
+) -> Result<Vec<(NaiveDate, f64)>> {
+    let mut days: Vec<(NaiveDate, f64)> = start.date()
+        .iter_days()
+        .take_while(|day| day.and_hms_opt(0, 0, 0).unwrap() < end)
+        .map(|day| (day, 0.0))
+        .collect();
+
+    for (_, day, hours) in queries::get_period_sessions(conn, start, end, topics)? {
+        if let Some((_, total)) = days.iter_mut().find(|(d, _)| *d == day) {
+            *total += hours;
+        }
+    }
+    Ok(days)
+}
+
+/// The periods `show` reports on and the kind of period their targets are
+/// for. None means no period was asked for and sessions are listed instead.
+fn show_ranges(args: &ShowArgs, now: chrono::NaiveDateTime) -> Result<Option<(Period, Vec<periods::Range>)>> {
+    let today = now.date();
+    let not_ahead = |date: NaiveDate| {
+        if date > today {
+            anyhow::bail!("{} is in the future", settings::date(date));
+        }
+        Ok(date)
+    };
+
+    if let Some(from) = &args.from {
+        let from = not_ahead(queries::parse_date(from)?)?;
+        let to = match &args.to {
+            Some(to) => queries::parse_date(to)?.min(today),
+            None => today,
+        };
+        if to < from {
+            anyhow::bail!("--to {} is before --from {}", settings::date(to), settings::date(from));
+        }
+        let days = from.iter_days()
+            .take_while(|day| *day <= to)
+            .map(|day| periods::containing(Period::Day, day, now))
+            .collect();
+        return Ok(Some((Period::Day, days)));
+    }
+
+    if let Some(date) = &args.date {
+        let date = not_ahead(queries::parse_date(date)?)?;
+        let period = args.period.unwrap_or(Period::Day);
+        return Ok(Some((period, vec![periods::containing(period, date, now)])));
+    }
+
+    Ok(args.period.map(|period| {
+        let ranges = (args.offset..args.offset + args.count)
+            .map(|back| periods::range(period, back, now))
+            .collect();
+        (period, ranges)
+    }))
+}
+
+pub fn list(conn: &Connection, count: usize, topics: &TopicArgs) -> Result<()> {
+    let sessions_with_hours = queries::get_sessions_with_calculated_hours(conn, count, &topic_filter(topics))?;
     display::print_sessions_with_hours(&sessions_with_hours, true);
     Ok(())
 }
 
-pub fn reset(conn: &Connection) -> Result<()> {
+pub fn reset(conn: &Connection, ids: &[String], filter_args: &FilterArgs, yes: bool) -> Result<()> {
+    let filter = build_filter(ids, filter_args)?;
+
+    if filter.is_empty() {
+        // A full reset removes the trash as well
+        let everything = queries::SessionFilter { include_trashed: true, ..Default::default() };
+        ensure_removable(conn, &queries::get_matching_sessions(conn, &everything)?)?;
+        println!("WARNING: This will delete ALL your time tracking data!");
+        println!("You can bring it back with 'walrus undo'.");
+        if !yes && !confirm("Type 'confirm' to proceed: ", &["confirm"])? {
+            println!("Reset cancelled");
+            return Ok(());
+        }
+
+        let snapshot = backup::snapshot(conn, "reset")?;
+        journal::transaction(conn, "reset", queries::delete_all_sessions)?;
+        println!("All data cleared (snapshot saved to {})", snapshot.display());
+        return Ok(());
+    }
+
+    let sessions = queries::get_matching_sessions(conn, &filter)?;
+    if sessions.is_empty() {
+        println!("No sessions match");
+        return Ok(());
+    }
+    ensure_removable(conn, &sessions)?;
+
+    println!("The following sessions will be permanently deleted:");
+    display::print_sessions_with_hours(&sessions, true);
+    if !yes && !confirm("Type 'confirm' to proceed: ", &["confirm"])? {
+        println!("Reset cancelled");
+        return Ok(());
+    }
+
+    let snapshot = backup::snapshot(conn, "reset")?;
+    journal::transaction(conn, &format!("reset {} sessions", sessions.len()), |tx| {
+        for (session, _) in &sessions {
+            queries::purge_session(tx, session.id)?;
+        }
+        Ok(())
+    })?;
+
+    println!("Deleted {} (snapshot saved to {})", removal_summary(&sessions), snapshot.display());
+    Ok(())
+}
+
+pub fn delete(conn: &Connection, ids: &[String], filter_args: &FilterArgs, yes: bool) -> Result<()> {
+    let filter = build_filter(ids, filter_args)?;
+    if filter.is_empty() {
+        anyhow::bail!("Specify session ids or a filter (see 'walrus delete --help')");
+    }
+
+    let sessions = queries::get_matching_sessions(conn, &filter)?;
+
+    // A single explicit id behaves like it always did: no preview, just do it
+    if let [(id, last)] = filter.ids[..] {
+        if id == last && filter_args_empty(filter_args) {
+            let deleted = journal::transaction(conn, &format!("delete session {}", id), |tx| {
+                queries::delete_session(tx, id)
+            })?;
+            if !deleted {
+                anyhow::bail!("Session with ID {} not found", id);
+            }
+            println!("Moved session {} to trash (restore with 'walrus trash restore {}')", id, id);
+            return Ok(());
+        }
+    }
+
+    if sessions.is_empty() {
+        println!("No sessions match");
+        return Ok(());
+    }
+    ensure_none_locked(&sessions)?;
+
+    println!("The following sessions will be moved to the trash:");
+    display::print_sessions_with_hours(&sessions, true);
+    if !yes && !confirm(&format!("Delete {} session(s)? [y/N] ", sessions.len()), &["y", "yes"])? {
+        println!("Delete cancelled");
+        return Ok(());
+    }
+
+    backup::snapshot(conn, "delete")?;
+    journal::transaction(conn, &format!("delete {} sessions", sessions.len()), |tx| {
+        for (session, _) in &sessions {
+            queries::delete_session(tx, session.id)?;
+        }
+        Ok(())
+    })?;
+
+    println!("Moved {} to trash (restore with 'walrus trash restore <id>')", removal_summary(&sessions));
+    Ok(())
+}
+
+fn build_filter(ids: &[String], args: &FilterArgs) -> Result<queries::SessionFilter> {
+    let ids = ids.iter()
+        .flat_map(|s| s.split(','))
+        .filter(|s| !s.is_empty())
+        .map(queries::parse_id_range)
+        .collect::<Result<Vec<_>>>()?;
+
+    let parse_bound = |value: &Option<String>, inclusive_day: bool| -> Result<Option<String>> {
+        value.as_deref().map(|s| {
+            let (dt, date_only) = queries::parse_date_or_datetime(s)?;
+            let dt = if date_only && inclusive_day { dt + Duration::days(1) } else { dt };
+            queries::to_rfc3339(dt)
+        }).transpose()
+    };
+
+    Ok(queries::SessionFilter {
+        ids,
+        topics: queries::TopicFilter { include: args.topic.clone(), ..Default::default() },
+        before: parse_bound(&args.before, false)?,
+        from: parse_bound(&args.from, false)?,
+        to: parse_bound(&args.to, true)?,
+        ..Default::default()
+    })
+}
+
+fn topic_filter(args: &TopicArgs) -> queries::TopicFilter {
+    queries::TopicFilter {
+        include: args.topic.clone(),
+        exclude: args.exclude_topic.clone(),
+    }
+}
+
+fn filter_args_empty(args: &FilterArgs) -> bool {
+    args.topic.is_empty() && args.before.is_none() && args.from.is_none() && args.to.is_none()
+}
+
+/// Checks sessions can be deleted for good: none locked, none on an invoice.
+fn ensure_removable(conn: &Connection, sessions: &[(queries::Session, f64)]) -> Result<()> {
+    ensure_none_locked(sessions)?;
+    for (session, _) in sessions {
+        queries::ensure_not_invoiced(conn, session.id)?;
+    }
+    Ok(())
+}
+
+fn ensure_none_locked(sessions: &[(queries::Session, f64)]) -> Result<()> {
+    let locked: Vec<_> = sessions.iter()
+        .filter(|(s, _)| s.locked)
+        .map(|(s, _)| s.id.to_string())
+        .collect();
+    if !locked.is_empty() {
+        anyhow::bail!(
+            "{} of these sessions are locked ({}). Unlock them first or narrow the filter",
+            locked.len(), locked.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn removal_summary(sessions: &[(queries::Session, f64)]) -> String {
+    let hours: f64 = sessions.iter().map(|(_, h)| h).sum();
+    format!("{} session(s) ({})", sessions.len(), settings::hours(hours))
+}
+
+/// Runs before every command. In a terminal, asks where to cap each
+/// forgotten session; otherwise only warns about them.
+pub fn check_stale(conn: &Connection, config: &Config) -> Result<()> {
+    use std::io::IsTerminal;
+
+    let stale = stale::find(conn, &config.stale, Local::now())?;
+    if stale.is_empty() {
+        return Ok(());
+    }
+
+    let interactive = config.stale.prompt && std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+    if !interactive {
+        for s in &stale {
+            eprintln!(
+                "Warning: session {} ({}) has been running for {:.1}h. Cap it with 'walrus fix-stale'",
+                s.session.id, s.session.topic, s.hours
+            );
+        }
+        return Ok(());
+    }
+
+    let threshold = stale::parse_threshold(&config.stale)?;
+    for s in &stale {
+        display::print_stale_session(s);
+        loop {
+            let answer = ask(&stale_prompt(s, threshold))?;
+            let end = match answer.to_lowercase().as_str() {
+                "" => break,
+                "d" => s.default_end(threshold),
+                "l" if s.last_activity.is_some() => s.last_activity.unwrap(),
+                _ => match s.parse_end(&answer) {
+                    Ok(end) => end,
+                    Err(e) => {
+                        println!("{}", e);
+                        continue;
+                    }
+                },
+            };
+            if let Err(e) = s.check_end(end) {
+                println!("{}", e);
+                continue;
+            }
+            cap_session(conn, s, end)?;
+            break;
+        }
+    }
+    println!();
+    Ok(())
+}
+
+fn stale_prompt(s: &stale::StaleSession, threshold: Duration) -> String {
+    let default = match s.end_of_day {
+        Some(_) => "end of day",
+        None => "after threshold",
+    };
+    let mut prompt = format!("Cap at [d] {} {}", default, s.default_end(threshold).format("%d.%m. %H:%M"));
+    if let Some(last) = s.last_activity {
+        prompt.push_str(&format!(", [l] last activity {}", last.format("%d.%m. %H:%M")));
+    }
+    prompt.push_str(", a time (HH:MM), or Enter to keep it running: ");
+    prompt
+}
+
+fn cap_session(conn: &Connection, s: &stale::StaleSession, end: chrono::NaiveDateTime) -> Result<()> {
+    let end_str = queries::to_rfc3339(end)?;
+    journal::transaction(conn, &format!("cap session {}", s.session.id), |tx| {
+        queries::update_session_end(tx, s.session.id, &end_str)
+    })?;
+    println!("Stopped session {} ({}) at {}", s.session.id, s.session.topic, settings::naive_datetime(&end));
+    Ok(())
+}
+
+pub fn pomodoro(conn: &Connection, topic: String, work: &str, pause: &str, cycles: u32, record_breaks: bool) -> Result<()> {
+    if cycles == 0 {
+        anyhow::bail!("Cycles must be at least 1");
+    }
+
+    let settings = pomodoro::Settings {
+        topic,
+        work: pomodoro::parse_interval(work)?,
+        pause: pomodoro::parse_interval(pause)?,
+        cycles,
+        record_breaks,
+    };
+    pomodoro::run(conn, &settings)
+}
+
+pub fn fix_stale(conn: &Connection, config: &Config, at: Option<String>, last_activity: bool, dry_run: bool) -> Result<()> {
+    let stale = stale::find(conn, &config.stale, Local::now())?;
+    if stale.is_empty() {
+        println!("No forgotten sessions");
+        return Ok(());
+    }
+
+    let threshold = stale::parse_threshold(&config.stale)?;
+    for s in &stale {
+        let end = match (&at, s.last_activity) {
+            (Some(at), _) => s.parse_end(at)?,
+            (None, Some(last)) if last_activity => last,
+            _ => s.default_end(threshold),
+        };
+
+        if dry_run {
+            display::print_stale_session(s);
+            println!("  would stop at {}", settings::naive_datetime(&end));
+        } else {
+            cap_session(conn, s, end)?;
+        }
+    }
+    Ok(())
+}
+
+/// Prints `prompt` and returns the trimmed line read from stdin.
+fn ask(prompt: &str) -> Result<String> {
     use std::io::{self, Write};
 
-    println!("WARNING: This will delete ALL your time tracking data!");
-    println!("This action cannot be undone.");
-    print!("Type 'confirm' to proceed: ");
+    print!("{}", prompt);
     io::stdout().flush()?;
 
     let mut input = String::new();
     io::stdin().read_line(&mut input)?;
-    let input = input.trim();
+    Ok(input.trim().to_string())
+}
 
-    if input != "confirm" {
-        println!("Reset cancelled");
-        return Ok(());
-    }
+/// Asks a question on stdin, returning true if the answer is one of `accepted`
+/// (case-insensitive).
+fn confirm(prompt: &str, accepted: &[&str]) -> Result<bool> {
+    let input = ask(prompt)?;
+    Ok(accepted.iter().any(|a| a.eq_ignore_ascii_case(&input)))
+}
 
-    queries::delete_all_sessions(conn)?;
-    println!("All data cleared");
+pub fn trash_list(conn: &Connection) -> Result<()> {
+    let sessions = queries::get_trashed_sessions(conn)?;
+    display::print_trash(&sessions);
     Ok(())
 }
 
-pub fn delete(conn: &Connection, id: i64) -> Result<()> {
-    if !queries::delete_session(conn, id)? {
-        anyhow::bail!("Session with ID {} not found", id);
+pub fn trash_restore(conn: &Connection, id: i64) -> Result<()> {
+    let restored = journal::transaction(conn, &format!("restore session {}", id), |tx| {
+        queries::restore_session(tx, id)
+    })?;
+    if !restored {
+        anyhow::bail!("Session with ID {} is not in the trash", id);
+    }
+    println!("Restored session {}", id);
+    Ok(())
+}
+
+pub fn trash_empty(conn: &Connection, older_than: Option<String>, yes: bool) -> Result<()> {
+    let cutoff = older_than
+        .map(|age| queries::parse_age(&age).map(|age| Local::now() - age))
+        .transpose()?;
+
+    if cutoff.is_none() && !yes {
+        let count = queries::get_trashed_sessions(conn)?.len();
+        if count == 0 {
+            println!("The trash is empty");
+            return Ok(());
+        }
+        if !confirm(&format!("Permanently delete all {} session(s) in the trash? [y/N] ", count), &["y", "yes"])? {
+            println!("Nothing deleted");
+            return Ok(());
+        }
+    }
+
+    backup::snapshot(conn, "empty-trash")?;
+    let (removed, kept) = journal::transaction(conn, "empty trash", |tx| queries::empty_trash(tx, cutoff))?;
+    println!("Permanently deleted {} session(s)", removed);
+    if kept > 0 {
+        println!("Kept {} locked or invoiced session(s) in the trash", kept);
     }
-    println!("Deleted session {}", id);
     Ok(())
 }
 
-pub fn export(conn: &Connection) -> Result<()> {
+pub fn export(conn: &Connection, config: &Config) -> Result<()> {
     let sessions = queries::get_all_sessions_for_export(conn)?;
+    let pricing = billing::Pricing::load(conn, config)?;
+
+    // Rounded hours go in an extra column, the raw duration stays as it is
+    let rounded = if pricing.rounds() {
+        let entries: Vec<_> = sessions.iter()
+            .filter_map(|s| s.end.map(|end| {
+                let hours = end.signed_duration_since(s.start).num_seconds() as f64 / 3600.0;
+                (s.topic.clone(), s.start.date_naive(), hours)
+            }))
+            .collect();
+        Some(pricing.round_by_topic(&entries))
+    } else {
+        None
+    };
+    let mut rounded = rounded.into_iter().flatten();
 
     let timestamp = Local::now().format("%Y%m%d_%H%M%S");
     let filename = format!("walrus_export_{}.csv", timestamp);
@@ -123,14 +660,18 @@ pub fn export(conn: &Connection) -> Result<()> {
     let mut writer = std::fs::File::create(&filename)?;
     use std::io::Write;
 
-    writeln!(writer, "start,end,duration (hours),topic")?;
+    if pricing.rounds() {
+        writeln!(writer, "start,end,duration (hours),topic,rounded (hours)")?;
+    } else {
+        writeln!(writer, "start,end,duration (hours),topic")?;
+    }
 
     for session in sessions {
         if let Some(end) = session.end {
             let duration = end.signed_duration_since(session.start);
             let hours = duration.num_seconds() as f64 / 3600.0;
 
-            writeln!(
+            write!(
                 writer,
                 "{},{},{:.2},{}",
                 session.start.format("%Y-%m-%d %H:%M:%S"),
@@ -138,6 +679,10 @@ pub fn export(conn: &Connection) -> Result<()> {
                 hours,
                 session.topic
             )?;
+            match rounded.next() {
+                Some(rounded) => writeln!(writer, ",{:.2}", rounded)?,
+                None => writeln!(writer)?,
+            }
         }
     }
 
@@ -153,13 +698,15 @@ pub fn add(conn: &Connection, topic: String, start: String, end: String) -> Resu
         anyhow::bail!("End time must be after start time");
     }
 
-    queries::insert_session(conn, &topic, &start_dt, &end_dt)?;
+    journal::transaction(conn, &format!("add {}", topic), |tx| {
+        queries::insert_session(tx, &topic, &start_dt, &end_dt)
+    })?;
 
     let duration = end_dt.parse::<chrono::DateTime<chrono::FixedOffset>>()?
         .signed_duration_since(start_dt.parse::<chrono::DateTime<chrono::FixedOffset>>()?);
     let hours = duration.num_seconds() as f64 / 3600.0;
 
-    println!("Added: {} ({:.2}h)", topic, hours);
+    println!("Added: {} ({})", topic, settings::hours(hours));
     Ok(())
 }
 
@@ -168,150 +715,601 @@ pub fn edit(conn: &Connection, id: i64, topic: Option<String>, start: Option<Str
         anyhow::bail!("Session with ID {} not found", id);
     }
 
-    if let Some(t) = topic {
-        queries::update_session_topic(conn, id, &t)?;
+    // Parse everything up front so a bad value leaves the session untouched
+    let start_dt = start.map(|s| queries::parse_datetime(&s)).transpose()?;
+    let end_dt = end.map(|e| queries::parse_datetime(&e)).transpose()?;
+
+    journal::transaction(conn, &format!("edit session {}", id), |tx| {
+        if let Some(t) = &topic {
+            queries::update_session_topic(tx, id, t)?;
+        }
+
+        if let Some(s) = &start_dt {
+            queries::update_session_start(tx, id, s)?;
+        }
+
+        if let Some(e) = &end_dt {
+            queries::update_session_end(tx, id, e)?;
+        }
+
+        Ok(())
+    })?;
+
+    println!("Updated session {}", id);
+    Ok(())
+}
+
+pub fn topic_rename(conn: &Connection, old: &str, new: &str, yes: bool) -> Result<()> {
+    retopic(conn, &[old.to_string()], new, &format!("rename topic {} to {}", old, new), yes)
+}
+
+pub fn topic_merge(conn: &Connection, topics: &[String], into: &str, yes: bool) -> Result<()> {
+    retopic(conn, topics, into, &format!("merge {} into {}", topics.join(", "), into), yes)
+}
+
+/// Moves all sessions of `topics` over to `target` after showing a preview.
+fn retopic(conn: &Connection, topics: &[String], target: &str, description: &str, yes: bool) -> Result<()> {
+    let usage: Vec<_> = queries::get_topic_usage(conn, topics)?
+        .into_iter()
+        .filter(|u| u.topic != target)
+        .collect();
+
+    if usage.is_empty() {
+        println!("No sessions to change");
+        return Ok(());
+    }
+
+    // Only one session per topic may be running, so two active ones can't be merged
+    let mut involved = topics.to_vec();
+    involved.push(target.to_string());
+    let active: usize = queries::get_topic_usage(conn, &involved)?.iter().map(|u| u.active).sum();
+    if active > 1 {
+        anyhow::bail!("More than one of these topics has an active session. Stop all but one first");
     }
 
-    if let Some(s) = start {
-        let start_dt = queries::parse_datetime(&s)?;
-        queries::update_session_start(conn, id, &start_dt)?;
+    display::print_topic_changes(&usage, target);
+    let total: usize = usage.iter().map(|u| u.sessions).sum();
+    if !yes && !confirm(&format!("Change {} session(s)? [y/N] ", total), &["y", "yes"])? {
+        println!("Cancelled");
+        return Ok(());
     }
 
-    if let Some(e) = end {
-        let end_dt = queries::parse_datetime(&e)?;
-        queries::update_session_end(conn, id, &end_dt)?;
+    let changed = journal::transaction(conn, description, |tx| {
+        let mut changed = 0;
+        for u in &usage {
+            changed += queries::rename_topic(tx, &u.topic, target)?;
+        }
+        Ok(changed)
+    })?;
+
+    println!("Moved {} session(s) to '{}'", changed, target);
+    Ok(())
+}
+
+pub fn client_add(conn: &Connection, name: &str, currency: &str, rate: Option<f64>) -> Result<()> {
+    billing::add_client(conn, name, currency)?;
+    if let Some(rate) = rate {
+        let client = billing::get_client(conn, name)?;
+        billing::set_rate(conn, client.id, None, None, rate, None)?;
     }
+    println!("Added client: {}", name);
+    Ok(())
+}
 
-    println!("Updated session {}", id);
+pub fn client_list(conn: &Connection) -> Result<()> {
+    let clients = billing::get_clients(conn)?;
+    display::print_clients(&clients);
     Ok(())
 }
 
-fn show_days(conn: &Connection, count: usize) -> Result<()> {
-    let now = Local::now();
-    let mut periods = Vec::new();
+pub fn client_rounding(
+    conn: &Connection,
+    config: &Config,
+    name: &str,
+    mode: Option<RoundingMode>,
+    increment: Option<u32>,
+    per: Option<RoundingScope>,
+    clear: bool,
+) -> Result<()> {
+    let client = billing::get_client(conn, name)?;
 
-    for i in 0..count {
-        let days_back = i as i64;
-        let day_start = (now - Duration::days(days_back))
-            .date_naive()
-            .and_hms_opt(0, 0, 0)
-            .unwrap();
-        let day_end = if i == 0 {
-            now.naive_local()
-        } else {
-            day_start + Duration::days(1)
-        };
+    if clear {
+        billing::set_client_rounding(conn, client.id, None)?;
+    } else if mode.is_some() || increment.is_some() || per.is_some() {
+        // Unset options keep the client's current value, or the configured one
+        let mut rounding = client.rounding.clone().unwrap_or_else(|| config.rounding.clone());
+        rounding.mode = mode.unwrap_or(rounding.mode);
+        rounding.increment = increment.unwrap_or(rounding.increment);
+        rounding.per = per.unwrap_or(rounding.per);
+        billing::set_client_rounding(conn, client.id, Some(&rounding))?;
+    }
 
-        let label = if i == 0 {
-            "Today".to_string()
-        } else if i == 1 {
-            "Yesterday".to_string()
-        } else {
-            day_start.format("%A, %d.%m.%Y").to_string()
-        };
+    let client = billing::get_client(conn, name)?;
+    display::print_client_rounding(&client, &config.rounding);
+    Ok(())
+}
 
-        let topics = queries::get_period_stats(conn, day_start, day_end)?;
-        periods.push(queries::PeriodStats { label, topics });
+pub fn client_remove(conn: &Connection, name: &str, yes: bool) -> Result<()> {
+    let client = billing::get_client(conn, name)?;
+    let prompt = format!("Remove client '{}' with all its projects and rates? [y/N] ", client.name);
+    if !yes && !confirm(&prompt, &["y", "yes"])? {
+        println!("Cancelled");
+        return Ok(());
     }
 
-    display::print_period_stats(&periods);
+    billing::remove_client(conn, client.id)?;
+    println!("Removed client: {}", client.name);
     Ok(())
 }
 
-fn show_weeks(conn: &Connection, count: usize) -> Result<()> {
-    let now = Local::now();
-    let mut periods = Vec::new();
+pub fn project_add(conn: &Connection, name: &str, client: &str, topic: &str, non_billable: bool, rate: Option<f64>) -> Result<()> {
+    let client = billing::get_client(conn, client)?;
+    billing::add_project(conn, client.id, name, topic, !non_billable)?;
+    if let Some(rate) = rate {
+        let project = billing::get_project(conn, name)?;
+        billing::set_rate(conn, client.id, Some(project.id), None, rate, None)?;
+    }
+    println!("Added project: {} ({})", name, client.name);
+    Ok(())
+}
 
-    for i in 0..count {
-        let days_back = (i * 7) as i64;
-        let week_start = (now - Duration::days(days_back + now.weekday().num_days_from_monday() as i64))
-            .date_naive()
-            .and_hms_opt(0, 0, 0)
-            .unwrap();
-        let week_end = if i == 0 {
-            now.naive_local()
-        } else {
-            week_start + Duration::days(7)
-        };
+pub fn project_edit(conn: &Connection, name: &str, topic: Option<String>, billable: Option<bool>) -> Result<()> {
+    let project = billing::get_project(conn, name)?;
+    billing::update_project(conn, project.id, topic.as_deref(), billable)?;
+    println!("Updated project: {}", project.name);
+    Ok(())
+}
 
-        let label = format!("Week {} ({} - {})",
-                            week_start.format("%V"),
-                            week_start.format("%d.%m"),
-                            week_end.format("%d.%m.%Y")
-        );
+pub fn project_list(conn: &Connection) -> Result<()> {
+    let projects = billing::get_projects(conn)?;
+    display::print_projects(&projects);
+    Ok(())
+}
+
+pub fn project_remove(conn: &Connection, name: &str) -> Result<()> {
+    let project = billing::get_project(conn, name)?;
+    billing::remove_project(conn, project.id)?;
+    println!("Removed project: {}", project.name);
+    Ok(())
+}
 
-        let topics = queries::get_period_stats(conn, week_start, week_end)?;
-        periods.push(queries::PeriodStats { label, topics });
+pub fn rate_set(
+    conn: &Connection,
+    rate: f64,
+    client: Option<String>,
+    project: Option<String>,
+    topic: Option<String>,
+    from: Option<String>,
+) -> Result<()> {
+    let project = project.map(|p| billing::get_project(conn, &p)).transpose()?;
+    let client = match (&client, &project) {
+        (Some(name), _) => billing::get_client(conn, name)?,
+        (None, Some(project)) => billing::get_client(conn, &project.client)?,
+        (None, None) => anyhow::bail!("Specify --client or --project"),
+    };
+    if let Some(project) = &project {
+        if project.client_id != client.id {
+            anyhow::bail!("Project '{}' belongs to '{}', not '{}'", project.name, project.client, client.name);
+        }
     }
 
-    display::print_period_stats(&periods);
+    let from = from.map(|f| queries::parse_date(&f)).transpose()?;
+
+    billing::set_rate(conn, client.id, project.as_ref().map(|p| p.id), topic.as_deref(), rate, from)?;
+    println!("Rate set: {}/h", billing::format_money(rate, &client.currency));
     Ok(())
 }
 
-fn show_months(conn: &Connection, count: usize) -> Result<()> {
-    let now = Local::now();
-    let mut periods = Vec::new();
+pub fn rate_list(conn: &Connection) -> Result<()> {
+    let rates = billing::get_rates(conn)?;
+    let clients = billing::get_clients(conn)?;
+    display::print_rates(&rates, &clients);
+    Ok(())
+}
 
-    for i in 0..count {
-        let months_back = i as i32;
-        let target_date = if months_back == 0 {
-            now.date_naive()
-        } else {
-            let year = now.year();
-            let month = now.month() as i32;
-            let new_month = ((month - 1 - months_back).rem_euclid(12)) + 1;
-            let new_year = year + (month - 1 - months_back).div_euclid(12);
-            NaiveDate::from_ymd_opt(new_year, new_month as u32, 1).unwrap()
-        };
+pub fn rate_remove(conn: &Connection, id: i64) -> Result<()> {
+    if !billing::remove_rate(conn, id)? {
+        anyhow::bail!("Rate with ID {} not found", id);
+    }
+    println!("Removed rate {}", id);
+    Ok(())
+}
 
-        let start = target_date.with_day(1).unwrap().and_hms_opt(0, 0, 0).unwrap();
-        let end = if i == 0 {
-            now.naive_local()
-        } else {
-            let next_month = if target_date.month() == 12 {
-                NaiveDate::from_ymd_opt(target_date.year() + 1, 1, 1).unwrap()
-            } else {
-                NaiveDate::from_ymd_opt(target_date.year(), target_date.month() + 1, 1).unwrap()
+pub fn target_set(conn: &Connection, hours: f64, per: Period, topic: Option<String>) -> Result<()> {
+    if !hours.is_finite() || hours <= 0.0 {
+        anyhow::bail!("Target hours must be a positive number");
+    }
+    targets::set_target(conn, topic.as_deref(), &per, hours)?;
+    println!(
+        "Target set: {} per {} for {}",
+        settings::hours(hours),
+        targets::period_name(&per),
+        topic.as_deref().unwrap_or("all topics")
+    );
+    Ok(())
+}
+
+pub fn target_list(conn: &Connection) -> Result<()> {
+    let targets = targets::get_targets(conn)?;
+    display::print_targets(&targets);
+    Ok(())
+}
+
+pub fn target_remove(conn: &Connection, id: i64) -> Result<()> {
+    if !targets::remove_target(conn, id)? {
+        anyhow::bail!("Target with ID {} not found", id);
+    }
+    println!("Removed target {}", id);
+    Ok(())
+}
+
+pub fn schedule_set(conn: &Connection, hours: &[f64], from: Option<String>) -> Result<()> {
+    let hours: [f64; 7] = match *hours {
+        [h] => [h, h, h, h, h, 0.0, 0.0],
+        [mon, tue, wed, thu, fri, sat, sun] => [mon, tue, wed, thu, fri, sat, sun],
+        _ => anyhow::bail!("Give one value for Monday to Friday, or seven values for Monday to Sunday"),
+    };
+    if hours.iter().any(|h| !h.is_finite()) {
+        anyhow::bail!("Hours per day must be numbers");
+    }
+    if hours.iter().any(|h| *h < 0.0 || *h > 24.0) {
+        anyhow::bail!("Hours per day must be between 0 and 24");
+    }
+
+    let from = match from {
+        Some(f) => queries::parse_date(&f)?,
+        None => Local::now().date_naive(),
+    };
+
+    schedule::set_schedule(conn, from, hours)?;
+    println!("Schedule set from {}: {} per week", settings::date(from), settings::hours(hours.iter().sum::<f64>()));
+    Ok(())
+}
+
+pub fn schedule_list(conn: &Connection) -> Result<()> {
+    let schedules = schedule::get_schedules(conn)?;
+    display::print_schedules(&schedules);
+    Ok(())
+}
+
+pub fn schedule_remove(conn: &Connection, id: i64) -> Result<()> {
+    if !schedule::remove_schedule(conn, id)? {
+        anyhow::bail!("Schedule with ID {} not found", id);
+    }
+    println!("Removed schedule {}", id);
+    Ok(())
+}
+
+pub fn balance(conn: &Connection, period: Period, count: usize) -> Result<()> {
+    let schedules = schedule::get_schedules(conn)?;
+    if schedules.is_empty() {
+        anyhow::bail!("No work schedule yet. Set one with e.g. 'walrus schedule set 8 --from 01.01.2025'");
+    }
+
+    let rows = schedule::balance(conn, &schedules, period, Local::now().naive_local())?;
+    display::print_balance(&rows, count);
+    Ok(())
+}
+
+pub fn off_add(
+    conn: &Connection,
+    date: &str,
+    to: Option<String>,
+    kind: OffKind,
+    half: bool,
+    name: Option<String>,
+) -> Result<()> {
+    let first = queries::parse_date(date)?;
+    let last = to.map(|t| queries::parse_date(&t)).transpose()?;
+    if last.is_some_and(|l| l < first) {
+        anyhow::bail!("--to must not be before the first day");
+    }
+
+    // In a range, skip days without expected hours like weekends
+    let schedules = schedule::get_schedules(conn)?;
+    let days: Vec<_> = match last {
+        Some(last) => holidays::days_between(first, last)
+            .filter(|d| schedules.is_empty() || schedule::expected_hours(&schedules, &[], *d) > 0.0)
+            .collect(),
+        None => vec![first],
+    };
+
+    let mut replaced = 0;
+    for &day in &days {
+        let absence = absences::Absence { day, kind, half, name: name.clone() };
+        if absences::set_absence(conn, &absence)? {
+            replaced += 1;
+        }
+    }
+
+    println!(
+        "Recorded {} day(s) off as {}{}",
+        days.len(),
+        absences::kind_name(&kind),
+        if replaced > 0 { format!(" ({} replaced)", replaced) } else { String::new() }
+    );
+    Ok(())
+}
+
+pub fn off_list(conn: &Connection, year: Option<i32>) -> Result<()> {
+    let year = year.unwrap_or_else(|| Local::now().year());
+    let first = NaiveDate::from_ymd_opt(year, 1, 1).unwrap();
+    let last = NaiveDate::from_ymd_opt(year, 12, 31).unwrap();
+    let absences = absences::get_absences(conn, first, last)?;
+    display::print_absences(year, &absences);
+    Ok(())
+}
+
+pub fn off_remove(conn: &Connection, date: &str, to: Option<String>) -> Result<()> {
+    let first = queries::parse_date(date)?;
+    let last = to.map(|t| queries::parse_date(&t)).transpose()?.unwrap_or(first);
+    let removed = absences::remove_absences(conn, first, last)?;
+    if removed == 0 {
+        anyhow::bail!("No days off recorded for {}", date);
+    }
+    println!("Removed {} day(s) off", removed);
+    Ok(())
+}
+
+pub fn off_import(
+    conn: &Connection,
+    file: Option<String>,
+    country: Option<String>,
+    region: Option<String>,
+    year: Option<i32>,
+    kind: OffKind,
+) -> Result<()> {
+    let days = match (file, country) {
+        (Some(file), _) => {
+            let content = std::fs::read_to_string(&file)
+                .map_err(|e| anyhow::anyhow!("Could not read {}: {}", file, e))?;
+            holidays::parse_ics(&content)
+        }
+        (None, Some(country)) => {
+            let year = year.unwrap_or_else(|| Local::now().year());
+            holidays::builtin(&country, region.as_deref(), year)?
+        }
+        (None, None) => anyhow::bail!("Give an .ics file or --country"),
+    };
+
+    let mut imported = 0;
+    for (day, name) in &days {
+        let absence = absences::Absence { day: *day, kind, half: false, name: Some(name.clone()) };
+        if absences::add_absence_if_free(conn, &absence)? {
+            imported += 1;
+        }
+    }
+
+    println!("Imported {} day(s) off", imported);
+    if imported < days.len() {
+        println!("Skipped {} day(s) that already had an entry", days.len() - imported);
+    }
+    Ok(())
+}
+
+pub fn report_billing(conn: &Connection, config: &Config, month: Option<String>) -> Result<()> {
+    let first = match month {
+        Some(m) => billing::parse_month(&m)?,
+        None => Local::now().date_naive().with_day(1).unwrap(),
+    };
+    let next = first.checked_add_months(chrono::Months::new(1)).unwrap();
+
+    let pricing = billing::Pricing::load(conn, config)?;
+    let report = billing::billing_report(
+        conn,
+        &pricing,
+        first.and_hms_opt(0, 0, 0).unwrap(),
+        next.and_hms_opt(0, 0, 0).unwrap(),
+    )?;
+
+    display::print_billing_report(&first.format("%B %Y").to_string(), &report);
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn invoice(
+    conn: &Connection,
+    config: &Config,
+    client: &str,
+    from: Option<String>,
+    to: Option<String>,
+    per: InvoiceGrouping,
+    tax: f64,
+    output: Option<String>,
+    dry_run: bool,
+) -> Result<()> {
+    let client = billing::get_client(conn, client)?;
+
+    // Defaults to last month
+    let this_month = Local::now().date_naive().with_day(1).unwrap();
+    let from = match from {
+        Some(f) => queries::parse_date(&f)?,
+        None => this_month.checked_sub_months(chrono::Months::new(1)).unwrap(),
+    };
+    let to = match to {
+        Some(t) => queries::parse_date(&t)?,
+        None => this_month.pred_opt().unwrap(),
+    };
+    if to < from {
+        anyhow::bail!("--to must not be before --from");
+    }
+
+    let grouping = match per {
+        InvoiceGrouping::Topic => invoice::Grouping::Topic,
+        InvoiceGrouping::Day => invoice::Grouping::Day,
+    };
+    let pricing = billing::Pricing::load(conn, config)?;
+    let invoice = invoice::build(conn, &pricing, &client, from, to, grouping, tax)?;
+
+    display::print_invoice(&invoice);
+    if dry_run {
+        println!("Dry run, nothing was saved");
+        return Ok(());
+    }
+
+    let dir = std::path::PathBuf::from(output.unwrap_or_else(|| ".".to_string()));
+    let md_path = dir.join(format!("invoice_{}.md", invoice.number));
+    let html_path = dir.join(format!("invoice_{}.html", invoice.number));
+    std::fs::write(&md_path, invoice::to_markdown(&invoice))?;
+    std::fs::write(&html_path, invoice::to_html(&invoice))?;
+
+    if let Err(e) = invoice::save(conn, &client, &invoice) {
+        let _ = std::fs::remove_file(&md_path);
+        let _ = std::fs::remove_file(&html_path);
+        return Err(e);
+    }
+
+    println!("Invoice {} written to:", invoice.number);
+    println!("  {}", md_path.display());
+    println!("  {}", html_path.display());
+    println!("{} session(s) marked as invoiced", invoice.session_ids.len());
+    Ok(())
+}
+
+pub fn timesheet(
+    conn: &Connection,
+    week: Option<u32>,
+    month: Option<Option<String>>,
+    format: TimesheetFormat,
+    topics: &TopicArgs,
+) -> Result<()> {
+    let now = Local::now().naive_local();
+    let range = match (week, month) {
+        (_, Some(month)) => {
+            let first = match month {
+                Some(m) => billing::parse_month(&m)?,
+                None => now.date().with_day(1).unwrap(),
             };
-            next_month.and_hms_opt(0, 0, 0).unwrap()
-        };
+            if first > now.date() {
+                anyhow::bail!("{} is in the future", first.format("%B %Y"));
+            }
+            periods::containing(Period::Month, first, now)
+        }
+        (Some(number), None) => periods::numbered_week(number, now)
+            .ok_or_else(|| anyhow::anyhow!("There is no week {}", number))?,
+        (None, None) => periods::range(Period::Week, 0, now),
+    };
 
-        let label = target_date.format("%B %Y").to_string();
-        let topics = queries::get_period_stats(conn, start, end)?;
-        periods.push(queries::PeriodStats { label, topics });
+    let sheet = timesheet::build(conn, &range, &topic_filter(topics))?;
+    match format {
+        TimesheetFormat::Terminal => display::print_timesheet(&sheet),
+        TimesheetFormat::Csv => print!("{}", timesheet::to_csv(&sheet)),
+        TimesheetFormat::Markdown => print!("{}", timesheet::to_markdown(&sheet)),
     }
+    Ok(())
+}
 
-    display::print_period_stats(&periods);
+pub fn invoices(conn: &Connection) -> Result<()> {
+    let invoices = invoice::get_invoices(conn)?;
+    display::print_invoices(&invoices);
     Ok(())
 }
 
-fn show_years(conn: &Connection, count: usize) -> Result<()> {
-    let now = Local::now();
-    let mut periods = Vec::new();
+pub fn lock(conn: &Connection, until: Option<String>, sessions: &[i64]) -> Result<()> {
+    if until.is_none() && sessions.is_empty() {
+        let boundary = queries::get_lock_boundary(conn)?;
+        let locked = queries::count_locked_sessions(conn)?;
+        display::print_locks(boundary.map(queries::locked_until), locked);
+        return Ok(());
+    }
 
-    for i in 0..count {
-        let years_back = i as i32;
-        let target_year = now.year() - years_back;
+    if let Some(until) = until {
+        let until = queries::parse_date(&until)?;
+        if let Some(boundary) = queries::get_lock_boundary(conn)? {
+            let current = queries::locked_until(boundary);
+            if until < current {
+                anyhow::bail!(
+                    "Sessions up to {} are already locked. Run 'walrus unlock' first to lock less",
+                    settings::date(current)
+                );
+            }
+        }
+        queries::set_period_lock(conn, until)?;
+        println!("Locked all sessions up to {}", settings::date(until));
+    }
+
+    for &id in sessions {
+        if !queries::set_session_locked(conn, id, true)? {
+            anyhow::bail!("Session with ID {} not found", id);
+        }
+        println!("Locked session {}", id);
+    }
 
-        let start = NaiveDate::from_ymd_opt(target_year, 1, 1)
-            .unwrap()
-            .and_hms_opt(0, 0, 0)
-            .unwrap();
+    Ok(())
+}
 
-        let end = if i == 0 {
-            now.naive_local()
+pub fn unlock(conn: &Connection, sessions: &[i64]) -> Result<()> {
+    if sessions.is_empty() {
+        if queries::remove_period_lock(conn)? {
+            println!("Removed the period lock");
         } else {
-            NaiveDate::from_ymd_opt(target_year + 1, 1, 1)
-                .unwrap()
-                .and_hms_opt(0, 0, 0)
-                .unwrap()
-        };
+            println!("No period lock set");
+        }
+        return Ok(());
+    }
+
+    for &id in sessions {
+        if !queries::set_session_locked(conn, id, false)? {
+            anyhow::bail!("Session with ID {} not found", id);
+        }
+        println!("Unlocked session {}", id);
+    }
+
+    Ok(())
+}
+
+pub fn backup(conn: &Connection, path: Option<String>) -> Result<()> {
+    let default_name = format!("walrus_{}.db", Local::now().format("%Y%m%d_%H%M%S"));
+    let path = match path.map(std::path::PathBuf::from) {
+        Some(p) if p.is_dir() => p.join(default_name),
+        Some(p) => p,
+        None => backup::get_backup_dir().join(default_name),
+    };
+
+    backup::backup_to(conn, &path)?;
+    println!("Backup written to: {}", path.display());
+    Ok(())
+}
+
+pub fn restore(conn: &mut Connection, file: &str) -> Result<()> {
+    let path = backup::resolve(file)?;
+    let version = backup::check_backup(&path)?;
+
+    let snapshot = backup::snapshot(conn, "restore")?;
+    backup::restore_from(conn, &path)?;
+
+    println!("Restored from: {}", path.display());
+    if version < crate::db::SCHEMA_VERSION {
+        println!("Upgraded schema from version {} to {}", version, crate::db::SCHEMA_VERSION);
+    }
+    println!("Previous data saved to: {}", snapshot.display());
+    Ok(())
+}
+
+pub fn backups() -> Result<()> {
+    let backups = backup::list_backups()?;
+    display::print_backups(&backups);
+    Ok(())
+}
 
-        let label = format!("{}", target_year);
-        let topics = queries::get_period_stats(conn, start, end)?;
-        periods.push(queries::PeriodStats { label, topics });
+pub fn undo(conn: &Connection) -> Result<()> {
+    match journal::undo(conn)? {
+        Some(description) => println!("Undone: {}", description),
+        None => println!("Nothing to undo"),
     }
+    Ok(())
+}
 
-    display::print_period_stats(&periods);
+pub fn redo(conn: &Connection) -> Result<()> {
+    match journal::redo(conn)? {
+        Some(description) => println!("Redone: {}", description),
+        None => println!("Nothing to redo"),
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+pub fn history(conn: &Connection, count: usize) -> Result<()> {
+    let operations = journal::history(conn, count)?;
+    display::print_history(&operations);
+    Ok(())
+}
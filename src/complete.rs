@@ -0,0 +1,52 @@
+// Dynamic shell completion. The registration script from `walrus completions`
+// calls back into walrus, which answers through these functions. They read
+// the database read-only and stay quiet on errors, since anything printed
+// ends up as a suggestion.
+
+use std::ffi::OsStr;
+use clap_complete::CompletionCandidate;
+use crate::{db, queries};
+
+const RECENT_SESSIONS: usize = 50;
+
+fn matching(current: &OsStr, candidates: impl IntoIterator<Item = CompletionCandidate>) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    candidates.into_iter()
+        .filter(|c| c.get_value().to_string_lossy().starts_with(current.as_ref()))
+        .collect()
+}
+
+/// All topics, most recently used first.
+pub fn topics(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Ok(Some(conn)) = db::open_read_only() else {
+        return Vec::new();
+    };
+    let topics = queries::get_all_topics(&conn).unwrap_or_default();
+    matching(current, topics.into_iter().map(|(topic, sessions)| {
+        CompletionCandidate::new(topic).help(Some(format!("{} session(s)", sessions).into()))
+    }))
+}
+
+/// Topics with a running session.
+pub fn active_topics(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Ok(Some(conn)) = db::open_read_only() else {
+        return Vec::new();
+    };
+    let active = queries::get_active_sessions(&conn).unwrap_or_default();
+    matching(current, active.into_iter().map(|s| {
+        CompletionCandidate::new(s.topic).help(Some(format!("since {}", s.start.format("%d.%m. %H:%M")).into()))
+    }))
+}
+
+/// Ids of the most recent sessions.
+pub fn session_ids(current: &OsStr) -> Vec<CompletionCandidate> {
+    let Ok(Some(conn)) = db::open_read_only() else {
+        return Vec::new();
+    };
+    let sessions = queries::get_sessions(&conn, RECENT_SESSIONS, &queries::TopicFilter::default()).unwrap_or_default();
+    matching(current, sessions.into_iter().enumerate().map(|(i, s)| {
+        CompletionCandidate::new(s.id.to_string())
+            .help(Some(format!("{} {}", s.topic, s.start.format("%d.%m.%Y %H:%M")).into()))
+            .display_order(Some(i))
+    }))
+}
@@ -0,0 +1,273 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use crate::rounding::Rounding;
+use crate::stale;
+use crate::settings::{self, Charset, ColorChoice, DurationStyle};
+
+#[derive(Deserialize, Serialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub defaults: DefaultsConfig,
+    pub display: DisplayConfig,
+    pub input: InputConfig,
+    pub calendar: CalendarConfig,
+    pub topics: TopicsConfig,
+    /// Default rounding for reports, exports and invoices
+    pub rounding: Rounding,
+    pub stale: StaleConfig,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DefaultsConfig {
+    /// Topic for `walrus start` without one
+    pub topic: String,
+    /// Sessions shown by `walrus list`
+    pub list_size: usize,
+    /// Ask before deleting or changing many sessions
+    pub confirm: bool,
+}
+
+impl Default for DefaultsConfig {
+    fn default() -> Self {
+        DefaultsConfig { topic: "default".to_string(), list_size: 10, confirm: true }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    /// strftime format for dates in lists and reports
+    pub date_format: String,
+    pub time_format: String,
+    pub duration: DurationStyle,
+    pub color: ColorChoice,
+    /// Characters for rules and charts, guessed from the locale by default
+    pub charset: Charset,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig {
+            date_format: "%d.%m.%Y".to_string(),
+            time_format: "%H:%M".to_string(),
+            duration: DurationStyle::Decimal,
+            color: ColorChoice::Auto,
+            charset: Charset::Auto,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputConfig {
+    /// Date format accepted in arguments, on top of DD.MM.YYYY and YYYY-MM-DD
+    pub date_format: String,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        InputConfig { date_format: "%d.%m.%Y".to_string() }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalendarConfig {
+    /// First day of the week
+    pub week_start: String,
+    /// Month the (fiscal) year starts in, 1 for calendar years
+    pub fiscal_year_start: u32,
+}
+
+impl Default for CalendarConfig {
+    fn default() -> Self {
+        CalendarConfig { week_start: "monday".to_string(), fiscal_year_start: 1 }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TopicsConfig {
+    /// Splits topics like `acme/website/frontend` into levels
+    pub separator: String,
+}
+
+impl Default for TopicsConfig {
+    fn default() -> Self {
+        TopicsConfig { separator: "/".to_string() }
+    }
+}
+
+/// When a running session counts as forgotten.
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StaleConfig {
+    /// Maximum running time, e.g. `12h`
+    pub threshold: String,
+    /// Time of day (`HH:MM`) no session should run past
+    pub end_of_day: Option<String>,
+    /// Ask what to do with forgotten sessions whenever a command runs in a
+    /// terminal. Otherwise only warn.
+    pub prompt: bool,
+}
+
+impl Default for StaleConfig {
+    fn default() -> Self {
+        StaleConfig { threshold: "12h".to_string(), end_of_day: None, prompt: true }
+    }
+}
+
+pub fn get_config_path() -> PathBuf {
+    dirs::config_dir()
+        .expect("Could not find config directory")
+        .join("walrus")
+        .join("config.toml")
+}
+
+/// Loads the config file, falling back to defaults if there is none.
+pub fn load() -> Result<Config> {
+    let path = get_config_path();
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+
+    let content = std::fs::read_to_string(&path)?;
+    parse(&content).map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e))
+}
+
+fn parse(content: &str) -> Result<Config> {
+    let config: Config = toml::from_str(content)?;
+    settings::from_config(&config, None)?;
+    stale::check(&config.stale)?;
+    Ok(config)
+}
+
+/// Settings without a default value, listed even while they are unset.
+const OPTIONAL: &[&str] = &["stale.end_of_day"];
+
+/// Every setting as `section.key` with its value in effect, defaults included.
+/// Optional settings that are unset have no value.
+pub fn list(config: &Config) -> Result<Vec<(String, Option<toml::Value>)>> {
+    let table = toml::Table::try_from(config)?;
+    let mut entries = Vec::new();
+    for (section, values) in table {
+        match values {
+            toml::Value::Table(values) => {
+                for (key, value) in values {
+                    entries.push((format!("{}.{}", section, key), Some(value)));
+                }
+            }
+            value => entries.push((section, Some(value))),
+        }
+    }
+    for key in OPTIONAL {
+        if !entries.iter().any(|(k, _)| k == key) {
+            entries.push((key.to_string(), None));
+        }
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(entries)
+}
+
+/// A setting's value as it is typed on the command line, strings unquoted.
+pub fn value_text(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// The setting in effect for `key`, e.g. `display.date_format`, or None if
+/// it is an optional setting that is unset.
+pub fn get(config: &Config, key: &str) -> Result<Option<toml::Value>> {
+    list(config)?
+        .into_iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, value)| value)
+        .ok_or_else(|| anyhow::anyhow!("Unknown setting '{}'. See 'walrus config list'", key))
+}
+
+fn split_key(key: &str) -> Result<(&str, &str)> {
+    key.split_once('.')
+        .ok_or_else(|| anyhow::anyhow!("Settings look like section.key, e.g. display.date_format"))
+}
+
+/// Reads the config file as an editable document, so writing it back keeps
+/// everything the user set, comments and layout included.
+fn read_document() -> Result<toml_edit::DocumentMut> {
+    let path = get_config_path();
+    if !path.exists() {
+        return Ok(toml_edit::DocumentMut::new());
+    }
+    let content = std::fs::read_to_string(&path)?;
+    content.parse().map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e))
+}
+
+/// Checks the document is a valid config before writing it. Unknown keys are
+/// caught here too, since every section denies unknown fields.
+fn write_document(document: &toml_edit::DocumentMut, key: &str) -> Result<()> {
+    let content = document.to_string();
+    let config: Config = toml::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("Can't set {}: {}", key, e.message().trim()))?;
+    settings::from_config(&config, None)?;
+    stale::check(&config.stale)?;
+
+    let path = get_config_path();
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, content)?;
+    Ok(())
+}
+
+/// Sets `key` in the config file. The value is read as TOML if it is a TOML
+/// number, boolean or quoted string, otherwise as a plain string.
+pub fn set(key: &str, raw: &str) -> Result<()> {
+    let (section, name) = split_key(key)?;
+    let mut value = raw.parse::<toml_edit::Value>()
+        .ok()
+        // Times like 18:00 would read as TOML times, but settings take them as text
+        .filter(|v| !v.is_datetime())
+        .unwrap_or_else(|| raw.into());
+    value.decor_mut().clear();
+
+    let mut document = read_document()?;
+    let entry = document.entry(section).or_insert_with(toml_edit::table);
+    let Some(values) = entry.as_table_like_mut() else {
+        anyhow::bail!("'{}' in the config file is not a section", section);
+    };
+    match values.get_mut(name) {
+        // Replace the value in place, keeping the comments around it
+        Some(toml_edit::Item::Value(old)) => {
+            *value.decor_mut() = old.decor().clone();
+            *old = value;
+        }
+        _ => {
+            values.insert(name, toml_edit::Item::Value(value));
+        }
+    }
+    write_document(&document, key)
+}
+
+/// Removes `key` from the config file, so its default applies again.
+/// Returns false if it wasn't set.
+pub fn unset(key: &str) -> Result<bool> {
+    let (section, name) = split_key(key)?;
+    let mut document = read_document()?;
+    let removed = match document.get_mut(section).and_then(|item| item.as_table_like_mut()) {
+        Some(values) => {
+            let removed = values.remove(name).is_some();
+            if values.is_empty() {
+                document.remove(section);
+            }
+            removed
+        }
+        None => false,
+    };
+    if removed {
+        write_document(&document, key)?;
+    }
+    Ok(removed)
+}
@@ -1,23 +1,189 @@
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, OpenFlags, Result};
+use rusqlite::functions::FunctionFlags;
 use std::path::PathBuf;
+use crate::config::Config;
+use crate::topics;
 
-pub fn get_db_path() -> PathBuf {
-    let data_dir = dirs::data_local_dir()
-        .expect("Could not find local data directory")
-        .join("walrus");
+/// A schema change. `session_image` is set if it changes the session columns
+/// the undo journal records, which makes older journal entries unusable.
+struct Migration {
+    sql: &'static str,
+    session_image: bool,
+}
+
+// Schema changes applied on top of the base tables, in order. The database's
+// `user_version` records how many of them have run.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        sql: "ALTER TABLE sessions ADD COLUMN deleted_at TEXT",
+        session_image: true,
+    },
+    Migration {
+        sql: "CREATE TABLE clients (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            currency TEXT NOT NULL
+        );
+        CREATE TABLE projects (
+            id INTEGER PRIMARY KEY,
+            client_id INTEGER NOT NULL REFERENCES clients(id),
+            name TEXT NOT NULL,
+            topic TEXT NOT NULL,
+            billable INTEGER NOT NULL DEFAULT 1
+        );
+        CREATE TABLE rates (
+            id INTEGER PRIMARY KEY,
+            client_id INTEGER NOT NULL REFERENCES clients(id),
+            project_id INTEGER REFERENCES projects(id),
+            topic TEXT,
+            rate REAL NOT NULL,
+            effective_from TEXT
+        );",
+        session_image: false,
+    },
+    Migration {
+        sql: "CREATE TABLE invoices (
+            id INTEGER PRIMARY KEY,
+            number TEXT NOT NULL UNIQUE,
+            client_id INTEGER NOT NULL REFERENCES clients(id),
+            period_from TEXT NOT NULL,
+            period_to TEXT NOT NULL,
+            issued TEXT NOT NULL,
+            currency TEXT NOT NULL,
+            hours REAL NOT NULL,
+            subtotal REAL NOT NULL,
+            tax_rate REAL NOT NULL,
+            total REAL NOT NULL
+        );
+        ALTER TABLE sessions ADD COLUMN invoice_id INTEGER REFERENCES invoices(id);",
+        session_image: false,
+    },
+    Migration {
+        sql: "CREATE TABLE period_lock (until TEXT NOT NULL);
+        ALTER TABLE sessions ADD COLUMN locked INTEGER NOT NULL DEFAULT 0;
+        UPDATE sessions SET locked = 1 WHERE invoice_id IS NOT NULL;",
+        session_image: false,
+    },
+    Migration {
+        sql: "CREATE TABLE targets (
+            id INTEGER PRIMARY KEY,
+            topic TEXT,
+            period TEXT NOT NULL,
+            hours REAL NOT NULL
+        );",
+        session_image: false,
+    },
+    Migration {
+        sql: "CREATE TABLE schedules (
+            id INTEGER PRIMARY KEY,
+            effective_from TEXT NOT NULL UNIQUE,
+            monday REAL NOT NULL,
+            tuesday REAL NOT NULL,
+            wednesday REAL NOT NULL,
+            thursday REAL NOT NULL,
+            friday REAL NOT NULL,
+            saturday REAL NOT NULL,
+            sunday REAL NOT NULL
+        );",
+        session_image: false,
+    },
+    Migration {
+        sql: "CREATE TABLE absences (
+            id INTEGER PRIMARY KEY,
+            day TEXT NOT NULL UNIQUE,
+            kind TEXT NOT NULL,
+            fraction REAL NOT NULL,
+            name TEXT
+        );",
+        session_image: false,
+    },
+    Migration {
+        sql: "ALTER TABLE clients ADD COLUMN rounding_mode TEXT;
+        ALTER TABLE clients ADD COLUMN rounding_increment INTEGER;
+        ALTER TABLE clients ADD COLUMN rounding_per TEXT;",
+        session_image: false,
+    },
+    // The pomodoro mark lives on the session row, so it goes away with the
+    // session and undo restores it
+    Migration {
+        sql: "ALTER TABLE sessions ADD COLUMN pomodoro INTEGER NOT NULL DEFAULT 0",
+        session_image: true,
+    },
+];
+
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+const DB_FILE: &str = "walrus.db";
+
+fn data_dir() -> Option<PathBuf> {
+    Some(dirs::data_local_dir()?.join("walrus"))
+}
+
+pub fn get_data_dir() -> PathBuf {
+    let data_dir = data_dir().expect("Could not find local data directory");
 
     // Create directory if it doesn't exist
     std::fs::create_dir_all(&data_dir).expect("Could not create data directory");
 
-    data_dir.join("walrus.db")
+    data_dir
+}
+
+pub fn get_db_path() -> PathBuf {
+    get_data_dir().join(DB_FILE)
 }
 
-pub fn init_db() -> Result<Connection> {
+pub fn init_db(config: &Config) -> Result<Connection> {
     let db_path = get_db_path();
     let is_new = !db_path.exists();
 
     let conn = Connection::open(&db_path)?;
+    register_functions(&conn, config)?;
+    setup(&conn)?;
+
+    if is_new {
+        println!("Database created at: {}", db_path.display());
+    }
+
+    Ok(conn)
+}
 
+/// Opens the database read-only, without creating or migrating anything.
+/// Returns None if there is no database yet, or if it still needs migrating.
+pub fn open_read_only() -> Result<Option<Connection>> {
+    let Some(path) = data_dir().map(|dir| dir.join(DB_FILE)) else {
+        return Ok(None);
+    };
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    let conn = Connection::open_with_flags(&path, flags)?;
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version < SCHEMA_VERSION {
+        return Ok(None);
+    }
+    Ok(Some(conn))
+}
+
+/// Makes `topic_match(pattern, topic)` available to queries, using the
+/// configured topic separator.
+fn register_functions(conn: &Connection, config: &Config) -> Result<()> {
+    let separator = config.topics.separator.clone();
+    conn.create_scalar_function(
+        "topic_match",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        move |ctx| {
+            let pattern: String = ctx.get(0)?;
+            let topic: Option<String> = ctx.get(1)?;
+            Ok(topic.is_some_and(|t| topics::matches(&pattern, &t, &separator)))
+        },
+    )
+}
+
+/// Creates missing tables and brings the schema up to date.
+pub fn setup(conn: &Connection) -> Result<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS sessions (
             id INTEGER PRIMARY KEY,
@@ -28,9 +194,129 @@ pub fn init_db() -> Result<Connection> {
         [],
     )?;
 
-    if is_new {
-        println!("Database created at: {}", db_path.display());
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS operations (
+            id INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            status TEXT NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS journal (
+            id INTEGER PRIMARY KEY,
+            op_id INTEGER NOT NULL REFERENCES operations(id),
+            session_id INTEGER NOT NULL,
+            before TEXT,
+            after TEXT
+        )",
+        [],
+    )?;
+
+    migrate(conn)
+}
+
+fn migrate(conn: &Connection) -> Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    if version >= SCHEMA_VERSION {
+        return Ok(());
     }
 
-    Ok(conn)
-}
\ No newline at end of file
+    // Each step commits with its version, so a failed step leaves the
+    // database at the last one that worked
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute_batch(migration.sql)?;
+        if migration.session_image {
+            // Journaled row images only replay against the schema they were taken from
+            tx.execute_batch("DELETE FROM journal; DELETE FROM operations;")?;
+        }
+        tx.pragma_update(None, "user_version", i as i64 + 1)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+/// A fresh, fully migrated in-memory database for tests.
+#[cfg(test)]
+pub fn open_in_memory() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    register_functions(&conn, &Config::default()).unwrap();
+    setup(&conn).unwrap();
+    conn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_version(conn: &Connection) -> i64 {
+        conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap()
+    }
+
+    /// The base tables as they were before the first migration.
+    fn base_database() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE sessions (id INTEGER PRIMARY KEY, topic TEXT, start_time TEXT NOT NULL, end_time TEXT);
+             CREATE TABLE operations (id INTEGER PRIMARY KEY, description TEXT NOT NULL, created_at TEXT NOT NULL, status TEXT NOT NULL);
+             CREATE TABLE journal (id INTEGER PRIMARY KEY, op_id INTEGER NOT NULL REFERENCES operations(id),
+                                   session_id INTEGER NOT NULL, before TEXT, after TEXT);
+             INSERT INTO sessions (topic, start_time, end_time)
+                 VALUES ('acme', '2026-10-01T09:00:00+02:00', '2026-10-01T10:00:00+02:00');
+             INSERT INTO operations (description, created_at, status) VALUES ('add acme', '2026-10-01T10:00:00+02:00', 'done');
+             INSERT INTO journal (op_id, session_id, before, after) VALUES (1, 1, NULL, '{}');",
+        ).unwrap();
+        conn
+    }
+
+    #[test]
+    fn migrates_a_base_database_to_the_current_schema() {
+        let conn = base_database();
+        setup(&conn).unwrap();
+
+        assert_eq!(user_version(&conn), SCHEMA_VERSION);
+        let (topic, deleted_at, locked, pomodoro): (String, Option<String>, bool, bool) = conn.query_row(
+            "SELECT topic, deleted_at, locked, pomodoro FROM sessions WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        ).unwrap();
+        assert_eq!(topic, "acme");
+        assert_eq!(deleted_at, None);
+        assert!(!locked);
+        assert!(!pomodoro);
+
+        // The row images in the journal predate the session columns added since
+        let journal: i64 = conn.query_row("SELECT COUNT(*) FROM journal", [], |row| row.get(0)).unwrap();
+        assert_eq!(journal, 0);
+    }
+
+    #[test]
+    fn migrating_twice_changes_nothing() {
+        let conn = open_in_memory();
+        setup(&conn).unwrap();
+        assert_eq!(user_version(&conn), SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn failed_migration_keeps_the_last_version_that_worked() {
+        let conn = base_database();
+        conn.execute_batch(MIGRATIONS[0].sql).unwrap();
+        conn.pragma_update(None, "user_version", 1).unwrap();
+        // Makes the second migration fail
+        conn.execute_batch("CREATE TABLE clients (id INTEGER PRIMARY KEY)").unwrap();
+
+        assert!(setup(&conn).is_err());
+        assert_eq!(user_version(&conn), 1);
+        let rates: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name = 'rates'",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+        assert_eq!(rates, 0);
+    }
+}
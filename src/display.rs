@@ -1,20 +1,123 @@
-use crate::queries::{Session, PeriodStats};
-use chrono::Local;
+use crate::queries::{Session, PeriodStats, TopicUsage};
+use crate::journal::Operation;
+use crate::backup::BackupFile;
+use crate::billing::{self, BillingReport, Client, Project, Rate};
+use crate::invoice::{Invoice, InvoiceRecord};
+use crate::topics::{self, TopicNode};
+use crate::targets::{self, Progress, Target};
+use crate::schedule::{BalanceRow, Schedule};
+use crate::absences::{self, Absence};
+use crate::rounding::Rounding;
+use crate::stale::StaleSession;
+use crate::timesheet::{self, Timesheet};
+use crate::settings::{self, Color};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate};
+
+/// A horizontal line separating a table's header or total.
+fn rule(width: usize) -> String {
+    (if settings::get().unicode { "─" } else { "-" }).repeat(width)
+}
+
+/// A heavier line, above grand totals.
+fn double_rule(width: usize) -> String {
+    (if settings::get().unicode { "═" } else { "=" }).repeat(width)
+}
+
+pub fn print_active_sessions(sessions: &[Session]) {
+    if sessions.is_empty() {
+        return;
+    }
 
-pub fn print_active_session(session: &Session) {
     let now = Local::now();
-    let duration = now.signed_duration_since(session.start);
-    let hours = duration.num_seconds() as f64 / 3600.0;
-    println!("\nActive: {} ({:.2}h)\n", session.topic, hours);
+    println!();
+    for session in sessions {
+        let duration = now.signed_duration_since(session.start);
+        let hours = duration.num_seconds() as f64 / 3600.0;
+        println!("Active: {} ({})", session.topic, settings::hours(hours));
+    }
+    println!();
+}
+
+/// Time since `start` as H:MM:SS.
+pub fn format_elapsed(start: &DateTime<FixedOffset>) -> String {
+    let secs = Local::now().signed_duration_since(*start).num_seconds().max(0);
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Fills in `{topic}`, `{elapsed}` (H:MM), `{hours}` and `{start}` (HH:MM).
+pub fn format_prompt(format: &str, session: &Session) -> String {
+    let secs = Local::now().signed_duration_since(session.start).num_seconds().max(0);
+    format
+        .replace("{topic}", &session.topic)
+        .replace("{elapsed}", &format!("{}:{:02}", secs / 3600, secs / 60 % 60))
+        .replace("{hours}", &format!("{:.2}", secs as f64 / 3600.0))
+        .replace("{start}", &session.start.format("%H:%M").to_string())
+}
+
+/// One line of JSON for a waybar custom module.
+pub fn print_waybar(text: &str, tooltip: &str, class: &str) {
+    let escape = |s: &str| {
+        let mut out = String::new();
+        for c in s.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out
+    };
+    println!(
+        "{{\"text\": \"{}\", \"tooltip\": \"{}\", \"class\": \"{}\"}}",
+        escape(text), escape(tooltip), escape(class)
+    );
+}
+
+pub fn print_status(active: &[Session], today: f64, progress: &[Progress]) {
+    if active.is_empty() {
+        println!("No active session");
+    } else {
+        println!("{:<25} {:<17} {:>10}", "Topic", "Since", "Elapsed");
+        println!("{}", rule(54));
+        for session in active {
+            println!(
+                "{:<25} {:<17} {:>10}",
+                session.topic,
+                settings::datetime(&session.start),
+                format_elapsed(&session.start)
+            );
+        }
+    }
+    println!("\nToday: {}", settings::hours(today));
+    if !progress.is_empty() {
+        println!();
+        print_progress(progress);
+    }
+}
+
+pub fn print_stale_session(stale: &StaleSession) {
+    let start = format!("{} {}", stale.session.start.format("%a"), settings::datetime(&stale.session.start));
+    match stale.end_of_day {
+        Some(eod) => println!(
+            "Session {} ({}) started {} and is still running past the end of day ({}), {:.1}h in total",
+            stale.session.id, stale.session.topic, start, eod.format("%d.%m. %H:%M"), stale.hours
+        ),
+        None => println!(
+            "Session {} ({}) started {} and has been running for {:.1}h",
+            stale.session.id, stale.session.topic, start, stale.hours
+        ),
+    }
 }
 
 pub fn print_sessions(sessions: &[Session], show_id: bool) {
     if show_id {
         println!("\n{:<5} {:<20} {:<20} {:<20} {:>10}", "ID", "Topic", "Start", "End", "Hours");
-        println!("{}", "─".repeat(80));
+        println!("{}", rule(80));
     } else {
         println!("\n{:<20} {:<20} {:<20} {:>10}", "Topic", "Start", "End", "Hours");
-        println!("{}", "─".repeat(75));
+        println!("{}", rule(75));
     }
 
     for session in sessions {
@@ -24,27 +127,27 @@ pub fn print_sessions(sessions: &[Session], show_id: bool) {
 
             if show_id {
                 println!(
-                    "{:<5} {:<20} {:<20} {:<20} {:>9.2}h",
+                    "{:<5} {:<20} {:<20} {:<20} {:>10}",
                     session.id, session.topic,
-                    session.start.format("%d.%m.%Y %H:%M"),
-                    end.format("%d.%m.%Y %H:%M"),
-                    hours
+                    settings::datetime(&session.start),
+                    settings::datetime(&end),
+                    settings::hours(hours)
                 );
             } else {
                 println!(
-                    "{:<20} {:<20} {:<20} {:>9.2}h",
+                    "{:<20} {:<20} {:<20} {:>10}",
                     session.topic,
-                    session.start.format("%d.%m.%Y %H:%M"),
-                    end.format("%d.%m.%Y %H:%M"),
-                    hours
+                    settings::datetime(&session.start),
+                    settings::datetime(&end),
+                    settings::hours(hours)
                 );
             }
         } else if show_id {
             println!(
-                "{:<5} {:<20} {:<20} {:<20} {:>10}",
+                "{:<5} {:<20} {:<20} {} {:>10}",
                 session.id, session.topic,
-                session.start.format("%d.%m.%Y %H:%M"),
-                "ACTIVE",
+                settings::datetime(&session.start),
+                settings::paint(&format!("{:<20}", "ACTIVE"), Color::Green),
                 "-"
             );
         }
@@ -56,37 +159,38 @@ pub fn print_sessions(sessions: &[Session], show_id: bool) {
 pub fn print_sessions_with_hours(sessions_with_hours: &[(Session, f64)], show_id: bool) {
     if show_id {
         println!("\n{:<5} {:<20} {:<20} {:<20} {:>10}", "ID", "Topic", "Start", "End", "Hours");
-        println!("{}", "─".repeat(80));
+        println!("{}", rule(80));
     } else {
         println!("\n{:<20} {:<20} {:<20} {:>10}", "Topic", "Start", "End", "Hours");
-        println!("{}", "─".repeat(75));
+        println!("{}", rule(75));
     }
 
     for (session, hours) in sessions_with_hours {
         if let Some(end) = session.end {
             if show_id {
                 println!(
-                    "{:<5} {:<20} {:<20} {:<20} {:>9.2}h",
+                    "{:<5} {:<20} {:<20} {:<20} {:>10}{}",
                     session.id, session.topic,
-                    session.start.format("%d.%m.%Y %H:%M"),
-                    end.format("%d.%m.%Y %H:%M"),
-                    hours
+                    settings::datetime(&session.start),
+                    settings::datetime(&end),
+                    settings::hours(*hours),
+                    if session.locked { settings::paint("  locked", Color::Dim) } else { String::new() }
                 );
             } else {
                 println!(
-                    "{:<20} {:<20} {:<20} {:>9.2}h",
+                    "{:<20} {:<20} {:<20} {:>10}",
                     session.topic,
-                    session.start.format("%d.%m.%Y %H:%M"),
-                    end.format("%d.%m.%Y %H:%M"),
-                    hours
+                    settings::datetime(&session.start),
+                    settings::datetime(&end),
+                    settings::hours(*hours)
                 );
             }
         } else if show_id {
             println!(
-                "{:<5} {:<20} {:<20} {:<20} {:>10}",
+                "{:<5} {:<20} {:<20} {} {:>10}",
                 session.id, session.topic,
-                session.start.format("%d.%m.%Y %H:%M"),
-                "ACTIVE",
+                settings::datetime(&session.start),
+                settings::paint(&format!("{:<20}", "ACTIVE"), Color::Green),
                 "-"
             );
         }
@@ -95,10 +199,11 @@ pub fn print_sessions_with_hours(sessions_with_hours: &[(Session, f64)], show_id
     println!();
 }
 
-pub fn print_period_stats(stats: &[PeriodStats]) {
+pub fn print_period_stats(stats: &[PeriodStats], separator: &str, depth: Option<usize>, chart: bool) {
     println!();
 
     let mut grand_total: std::collections::HashMap<String, f64> = std::collections::HashMap::new();
+    let mut grand_progress: Vec<Progress> = Vec::new();
 
     for (i, period) in stats.iter().enumerate() {
         println!("{}", period.label);
@@ -107,11 +212,27 @@ pub fn print_period_stats(stats: &[PeriodStats]) {
         for (topic, hours) in &period.topics {
             total += hours;
             *grand_total.entry(topic.clone()).or_insert(0.0) += hours;
-            println!("  {:<20} {:>8.2}h", topic, hours);
         }
+        print_topic_tree(&topics::build_tree(&period.topics, separator, depth), chart);
 
-        println!("  {}", "─".repeat(30));
-        println!("  {:<20} {:>8.2}h", "Total", total);
+        println!("  {}", rule(30));
+        println!("  {:<20} {:>9}", "Total", settings::hours(total));
+        if let Some(rounded) = period.rounded_total {
+            println!("  {:<20} {:>9}", "Rounded", settings::hours(rounded));
+        }
+        print_absences_inline(&period.absences);
+        print_pomodoros_inline(&period.pomodoros);
+        print_progress(&period.progress);
+
+        for p in &period.progress {
+            match grand_progress.iter_mut().find(|g| g.label == p.label) {
+                Some(g) => {
+                    g.actual += p.actual;
+                    g.target += p.target;
+                }
+                None => grand_progress.push(Progress { label: p.label.clone(), actual: p.actual, target: p.target }),
+            }
+        }
 
         if i < stats.len() - 1 {
             println!();
@@ -119,20 +240,552 @@ pub fn print_period_stats(stats: &[PeriodStats]) {
     }
 
     if stats.len() > 1 {
-        println!("\n{}", "═".repeat(33));
+        println!("\n{}", double_rule(33));
         println!("Grand Total:");
 
-        let mut sorted: Vec<_> = grand_total.iter().collect();
-        sorted.sort_by(|a, b| b.1.partial_cmp(a.1).unwrap());
+        let grand_total: Vec<(String, f64)> = grand_total.into_iter().collect();
+        let total = grand_total.iter().fold(0.0, |sum, (_, h)| sum + h);
+        let rounded = stats.iter()
+            .filter_map(|p| p.rounded_total)
+            .reduce(|sum, h| sum + h);
+
+        print_topic_tree(&topics::build_tree(&grand_total, separator, depth), chart);
+        println!("  {}", rule(30));
+        println!("  {:<20} {:>9}", "Total", settings::hours(total));
+        if let Some(rounded) = rounded {
+            println!("  {:<20} {:>9}", "Rounded", settings::hours(rounded));
+        }
+        print_progress(&grand_progress);
+    }
+
+    println!();
+}
 
-        let total: f64 = sorted.iter().map(|(_, h)| *h).sum();
+fn print_absences_inline(absences: &[Absence]) {
+    if absences.is_empty() {
+        return;
+    }
+
+    println!("  Off");
+    for absence in absences {
+        println!("    {:<18} {}", settings::day(absence.day), absence.description());
+    }
+}
+
+fn print_pomodoros_inline(pomodoros: &[(NaiveDate, usize)]) {
+    if pomodoros.is_empty() {
+        return;
+    }
+
+    println!("  Pomodoros");
+    for (day, count) in pomodoros {
+        println!("    {:<18} {}", settings::day(*day), count);
+    }
+}
+
+/// Redraws the pomodoro countdown in place.
+pub fn print_countdown(phase: &str, cycle: u32, cycles: u32, topic: &str, remaining: std::time::Duration) {
+    use std::io::Write;
+
+    let secs = remaining.as_secs();
+    print!("\r{} {}/{}  {}  {:02}:{:02} ", phase, cycle, cycles, topic, secs / 60, secs % 60);
+    std::io::stdout().flush().ok();
+}
 
-        for (topic, hours) in sorted {
-            println!("  {:<20} {:>8.2}h", topic, hours);
+const PROGRESS_WIDTH: usize = 20;
+
+/// Prints one line per target: hours against the target, a bar and how much
+/// is left or how far over it is.
+fn print_progress(progress: &[Progress]) {
+    if progress.is_empty() {
+        return;
+    }
+
+    println!("  Targets");
+    for p in progress {
+        let ratio = if p.target > 0.0 { p.actual / p.target } else { 0.0 };
+        let filled = ((ratio * PROGRESS_WIDTH as f64).round() as usize).min(PROGRESS_WIDTH);
+        let (full, empty) = if settings::get().unicode { ("█", "░") } else { ("#", ".") };
+        let status = if p.difference() >= 0.0 {
+            settings::paint(&format!("{} over", settings::hours(p.difference())), Color::Green)
+        } else {
+            settings::paint(&format!("{} left", settings::hours(p.remaining())), Color::Yellow)
+        };
+        println!(
+            "    {:<18} {:>7} / {:>7} [{}{}] {:>4.0}%  {}",
+            p.label,
+            settings::hours(p.actual),
+            settings::hours(p.target),
+            full.repeat(filled),
+            empty.repeat(PROGRESS_WIDTH - filled),
+            ratio * 100.0,
+            status
+        );
+    }
+}
+
+/// Prints each level of the topic tree indented below its parent, with a
+/// bar scaled to the largest topic if `chart` is set.
+fn print_topic_tree(nodes: &[TopicNode], chart: bool) {
+    let max = nodes.iter().fold(0.0, |max: f64, node| max.max(node.hours));
+    let width = settings::terminal_width().saturating_sub(34).clamp(10, 80);
+    print_topic_level(nodes, 1, chart.then_some((max, width)));
+}
+
+fn print_topic_level(nodes: &[TopicNode], level: usize, chart: Option<(f64, usize)>) {
+    for node in nodes {
+        let label = format!("{}{}", "  ".repeat(level), node.name);
+        match chart {
+            Some((max, width)) => println!("{:<22} {:>9}  {}", label, settings::hours(node.hours), bar(node.hours, max, width)),
+            None => println!("{:<22} {:>9}", label, settings::hours(node.hours)),
         }
-        println!("  {}", "─".repeat(30));
-        println!("  {:<20} {:>8.2}h", "Total", total);
+        print_topic_level(&node.children, level + 1, chart);
+    }
+}
+
+/// A bar that is `width` columns long at `max`. Block characters draw
+/// eighths of a column, the ASCII fallback whole columns.
+fn bar(value: f64, max: f64, width: usize) -> String {
+    if max <= 0.0 {
+        return String::new();
+    }
+
+    let eighths = ((value / max).clamp(0.0, 1.0) * (width * 8) as f64).round() as usize;
+    if !settings::get().unicode {
+        return "#".repeat(eighths.div_ceil(8));
+    }
+
+    const PARTIAL: [&str; 8] = ["", "▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+    format!("{}{}", "█".repeat(eighths / 8), PARTIAL[eighths % 8])
+}
+
+/// Prints the hours of each day as one line of bars, scaled to the busiest
+/// day. Only the most recent days are shown if they don't fit the terminal.
+pub fn print_sparkline(days: &[(NaiveDate, f64)]) {
+    const UNICODE: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    const ASCII: [char; 9] = [' ', '_', '.', '-', '~', '=', '+', '*', '#'];
+
+    let (Some(_), Some((last, _))) = (days.first(), days.last()) else {
+        return;
+    };
+    let available = settings::terminal_width().saturating_sub(4).max(1);
+    let shown = &days[days.len().saturating_sub(available)..];
+    let cell = (available / shown.len()).clamp(1, 3);
+    let max = shown.iter().fold(0.0, |max: f64, (_, hours)| max.max(*hours));
+    let ramp = if settings::get().unicode { UNICODE } else { ASCII };
+
+    let line: String = shown.iter()
+        .map(|(_, hours)| {
+            let level = if *hours <= 0.0 || max <= 0.0 { 0 } else { 1 + (hours / max * 7.0).round() as usize };
+            ramp[level].to_string().repeat(cell)
+        })
+        .collect();
+
+    println!(
+        "Per day, {} - {} (max {})",
+        settings::date(shown[0].0), settings::date(*last), settings::hours(max)
+    );
+    println!("  {}\n", line);
+}
+
+pub fn print_topic_changes(usage: &[TopicUsage], target: &str) {
+    println!("\n{:<20} {:>10} {:>10}    {:<20}", "Topic", "Sessions", "Hours", "New topic");
+    println!("{}", rule(67));
+
+    for u in usage {
+        let active = if u.active > 0 { " (active)" } else { "" };
+        println!(
+            "{:<20} {:>10} {:>10} -> {}{}",
+            u.topic, u.sessions, settings::hours(u.hours), target, active
+        );
+    }
+
+    println!();
+}
+
+pub fn print_client_rounding(client: &Client, default: &Rounding) {
+    match &client.rounding {
+        Some(rounding) => println!("{}: rounded {}", client.name, rounding.describe()),
+        None => println!("{}: configured default ({})", client.name, default.describe()),
+    }
+}
+
+pub fn print_clients(clients: &[Client]) {
+    println!("\n{:<25} {:<10}", "Client", "Currency");
+    println!("{}", rule(36));
+
+    for client in clients {
+        println!("{:<25} {:<10}", client.name, client.currency);
+    }
+
+    println!();
+}
+
+pub fn print_projects(projects: &[Project]) {
+    println!("\n{:<20} {:<20} {:<25} {:<8}", "Project", "Client", "Topic", "Billable");
+    println!("{}", rule(76));
+
+    for project in projects {
+        println!(
+            "{:<20} {:<20} {:<25} {:<8}",
+            project.name, project.client, project.topic,
+            if project.billable { "yes" } else { "no" }
+        );
+    }
+
+    println!();
+}
+
+pub fn print_rates(rates: &[Rate], clients: &[Client]) {
+    println!("\n{:<5} {:<20} {:<20} {:<20} {:<12} {:>15}", "ID", "Client", "Project", "Topic", "From", "Rate");
+    println!("{}", rule(97));
+
+    for rate in rates {
+        let currency = clients.iter()
+            .find(|c| c.id == rate.client_id)
+            .map(|c| c.currency.as_str())
+            .unwrap_or("");
+        println!(
+            "{:<5} {:<20} {:<20} {:<20} {:<12} {:>15}",
+            rate.id,
+            rate.client,
+            rate.project.as_deref().unwrap_or("-"),
+            rate.topic.as_deref().unwrap_or("-"),
+            rate.effective_from.map(settings::date).unwrap_or_else(|| "-".to_string()),
+            billing::format_money(rate.rate, currency)
+        );
+    }
+
+    println!();
+}
+
+pub fn print_targets(targets: &[Target]) {
+    println!("\n{:<5} {:<30} {:<8} {:>10}", "ID", "Topic", "Per", "Hours");
+    println!("{}", rule(56));
+
+    for target in targets {
+        println!(
+            "{:<5} {:<30} {:<8} {:>10}",
+            target.id,
+            target.topic.as_deref().unwrap_or("(all)"),
+            targets::period_name(&target.period),
+            settings::hours(target.hours)
+        );
     }
 
     println!();
-}
\ No newline at end of file
+}
+
+pub fn print_schedules(schedules: &[Schedule]) {
+    println!(
+        "\n{:<5} {:<12} {:>5} {:>5} {:>5} {:>5} {:>5} {:>5} {:>5} {:>8}",
+        "ID", "From", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun", "Week"
+    );
+    println!("{}", rule(68));
+
+    for schedule in schedules {
+        let days: Vec<String> = schedule.hours.iter().map(|h| format!("{:>5}", settings::hours(*h))).collect();
+        println!(
+            "{:<5} {:<12} {} {:>8}",
+            schedule.id,
+            settings::date(schedule.effective_from),
+            days.join(" "),
+            settings::hours(schedule.weekly_hours())
+        );
+    }
+
+    println!();
+}
+
+/// Hours with a sign, e.g. `+1.50h`.
+fn with_sign(hours: f64) -> String {
+    let text = settings::hours(hours);
+    if text.starts_with('-') { text } else { format!("+{}", text) }
+}
+
+/// Hours with a sign, red when negative, padded to the balance columns.
+fn signed(hours: f64) -> String {
+    let text = format!("{:>10}", with_sign(hours));
+    if hours < 0.0 {
+        settings::paint(&text, Color::Red)
+    } else {
+        text
+    }
+}
+
+/// Prints the last `count` balance rows and the overall balance.
+pub fn print_balance(rows: &[BalanceRow], count: usize) {
+    println!("\n{:<32} {:>6} {:>10} {:>10} {:>10} {:>10}", "Period", "Off", "Expected", "Worked", "Diff", "Balance");
+    println!("{}", rule(83));
+
+    for row in &rows[rows.len().saturating_sub(count)..] {
+        let off = if row.days_off > 0.0 { format!("{}d", row.days_off) } else { "-".to_string() };
+        println!(
+            "{:<32} {:>6} {:>10} {:>10} {} {}",
+            row.label,
+            off,
+            settings::hours(row.expected),
+            settings::hours(row.worked),
+            signed(row.worked - row.expected),
+            signed(row.balance)
+        );
+    }
+
+    let balance = rows.last().map(|r| r.balance).unwrap_or(0.0);
+    let status = if balance >= 0.0 { "overtime" } else { "undertime" };
+    println!("\nBalance: {} {}\n", with_sign(balance), status);
+}
+
+pub fn print_absences(year: i32, absences: &[Absence]) {
+    println!("\nDays off in {}", year);
+    println!("{}", rule(60));
+
+    for absence in absences {
+        println!(
+            "{:<16} {:<10} {}",
+            settings::day(absence.day),
+            absences::kind_name(&absence.kind),
+            absence.description()
+        );
+    }
+
+    let mut totals: Vec<(&str, f64)> = Vec::new();
+    for absence in absences {
+        let kind = absences::kind_name(&absence.kind);
+        match totals.iter_mut().find(|(k, _)| *k == kind) {
+            Some((_, days)) => *days += absence.fraction(),
+            None => totals.push((kind, absence.fraction())),
+        }
+    }
+
+    if !totals.is_empty() {
+        println!();
+        for (kind, days) in totals {
+            println!("{:<10} {:>5} day(s)", kind, days);
+        }
+    }
+
+    println!();
+}
+
+pub fn print_billing_report(label: &str, report: &BillingReport) {
+    println!("\nBilling: {}", label);
+
+    for bill in &report.clients {
+        println!("\n{} ({})", bill.client, bill.currency);
+        if bill.rounding.is_active() {
+            println!("  Rounded {}", bill.rounding.describe());
+        }
+        println!("  {:<20} {:<25} {:>8} {:>8} {:>15} {:>15}", "Project", "Topic", "Hours", "Billed", "Rate", "Amount");
+        println!("  {}", rule(96));
+
+        for line in &bill.lines {
+            let rate = match (line.billable, line.rate) {
+                (false, _) => "not billable".to_string(),
+                (true, None) => "no rate".to_string(),
+                (true, Some(rate)) => billing::format_money(rate, &bill.currency),
+            };
+            println!(
+                "  {:<20} {:<25} {:>8} {:>8} {:>15} {:>15}",
+                line.project, line.topic, settings::hours(line.hours), settings::hours(line.rounded), rate,
+                billing::format_money(line.amount, &bill.currency)
+            );
+        }
+
+        println!("  {}", rule(96));
+        println!(
+            "  {:<46} {:>8} {:>8} {:>15} {:>15}",
+            "Subtotal", settings::hours(bill.hours()), settings::hours(bill.rounded_hours()), "",
+            billing::format_money(bill.total(), &bill.currency)
+        );
+        if bill.billable_hours() < bill.hours() {
+            println!("  {:<46} {:>8}", "of which billable", settings::hours(bill.billable_hours()));
+        }
+    }
+
+    if report.clients.is_empty() {
+        println!("\nNo billable time tracked");
+    }
+
+    if !report.unassigned.is_empty() {
+        println!("\nNot assigned to any project:");
+        for (topic, hours) in &report.unassigned {
+            println!("  {:<20} {:>9}", topic, settings::hours(*hours));
+        }
+    }
+
+    println!();
+}
+
+pub fn print_invoice(invoice: &Invoice) {
+    let money = |amount: f64| billing::format_money(amount, &invoice.currency);
+
+    println!(
+        "\nInvoice {} for {} ({} - {})",
+        invoice.number, invoice.client,
+        settings::date(invoice.from), settings::date(invoice.to)
+    );
+    println!("  {:<30} {:>8} {:>15} {:>15}", "Description", "Hours", "Rate", "Amount");
+    println!("  {}", rule(71));
+
+    for line in &invoice.lines {
+        let rate = line.rate.map(money).unwrap_or_else(|| "mixed".to_string());
+        println!("  {:<30} {:>8} {:>15} {:>15}", line.description, settings::hours(line.hours), rate, money(line.amount));
+    }
+
+    println!("  {}", rule(71));
+    println!("  {:<30} {:>8} {:>15} {:>15}", "Subtotal", settings::hours(invoice.hours()), "", money(invoice.subtotal()));
+    if invoice.rounding.is_active() {
+        println!("  {:<30} {:>8} (rounded {})", "Tracked", settings::hours(invoice.raw_hours), invoice.rounding.describe());
+    }
+    if invoice.tax_rate > 0.0 {
+        println!("  {:<30} {:>8} {:>15} {:>15}", format!("Tax ({}%)", invoice.tax_rate), "", "", money(invoice.tax()));
+    }
+    println!("  {:<30} {:>8} {:>15} {:>15}", "Total", "", "", money(invoice.total()));
+    println!();
+}
+
+pub fn print_invoices(invoices: &[InvoiceRecord]) {
+    println!(
+        "\n{:<10} {:<20} {:<12} {:<25} {:>8} {:>9} {:>15}",
+        "Number", "Client", "Issued", "Period", "Sessions", "Hours", "Total"
+    );
+    println!("{}", rule(105));
+
+    for invoice in invoices {
+        println!(
+            "{:<10} {:<20} {:<12} {:<25} {:>8} {:>9} {:>15}",
+            invoice.number,
+            invoice.client,
+            settings::date(invoice.issued),
+            format!("{} - {}", settings::date(invoice.from), settings::date(invoice.to)),
+            invoice.sessions,
+            settings::hours(invoice.hours),
+            billing::format_money(invoice.total, &invoice.currency)
+        );
+    }
+
+    println!();
+}
+
+pub fn print_locks(until: Option<NaiveDate>, locked_sessions: usize) {
+    match until {
+        Some(until) => println!("Period locked up to {}", settings::date(until)),
+        None => println!("No period lock"),
+    }
+    println!("{} session(s) locked individually", locked_sessions);
+}
+
+pub fn print_timesheet(sheet: &Timesheet) {
+    let width = sheet.rows.iter()
+        .map(|(topic, _)| topic.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(20);
+    let column = if sheet.days.len() <= 7 { 8 } else { 6 };
+    let cell = |hours: f64| if hours > 0.0 { settings::hours(hours) } else { "-".to_string() };
+
+    println!("\n{}", sheet.label);
+    print!("{:<width$}", "Topic");
+    for day in &sheet.days {
+        print!(" {:>column$}", sheet.heading(*day));
+    }
+    println!(" {:>9}", "Total");
+    let rule = rule(width + (column + 1) * sheet.days.len() + 10);
+    println!("{}", rule);
+
+    for (topic, hours) in &sheet.rows {
+        print!("{:<width$}", topic);
+        for h in hours {
+            print!(" {:>column$}", cell(*h));
+        }
+        println!(" {:>9}", settings::hours(timesheet::row_total(hours)));
+    }
+
+    println!("{}", rule);
+    print!("{:<width$}", "Total");
+    for h in sheet.day_totals() {
+        print!(" {:>column$}", cell(h));
+    }
+    println!(" {:>9}\n", settings::hours(sheet.total()));
+}
+
+pub fn print_trash(sessions: &[(Session, DateTime<FixedOffset>)]) {
+    println!("\n{:<5} {:<20} {:<20} {:<20} {:<20}", "ID", "Topic", "Start", "End", "Deleted");
+    println!("{}", rule(89));
+
+    for (session, deleted) in sessions {
+        let end = session.end
+            .map(|e| settings::datetime(&e))
+            .unwrap_or_else(|| "ACTIVE".to_string());
+        println!(
+            "{:<5} {:<20} {:<20} {:<20} {:<20}",
+            session.id, session.topic,
+            settings::datetime(&session.start),
+            end,
+            settings::datetime(deleted)
+        );
+    }
+
+    println!();
+}
+
+pub fn print_history(operations: &[Operation]) {
+    println!("\n{:<5} {:<20} {:<35} {:>8}", "ID", "Time", "Operation", "Changes");
+    println!("{}", rule(71));
+
+    for op in operations {
+        let description = if op.undone {
+            format!("{} (undone)", op.description)
+        } else {
+            op.description.clone()
+        };
+        println!(
+            "{:<5} {:<20} {:<35} {:>8}",
+            op.id,
+            settings::datetime(&op.created),
+            description,
+            op.changes
+        );
+    }
+
+    println!();
+}
+
+pub fn print_backups(backups: &[BackupFile]) {
+    println!("\n{:<45} {:<20} {:>10} {:<10}", "File", "Created", "Size", "Kind");
+    println!("{}", rule(88));
+
+    for backup in backups {
+        let name = backup.path.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let kind = if backup.automatic { "auto" } else { "manual" };
+        println!(
+            "{:<45} {:<20} {:>8}KB {:<10}",
+            name,
+            settings::naive_datetime(&backup.created.naive_local()),
+            backup.size.div_ceil(1024),
+            kind
+        );
+    }
+
+    if let Some(first) = backups.first() {
+        if let Some(dir) = first.path.parent() {
+            println!("\nLocation: {}", dir.display());
+        }
+    }
+
+    println!();
+}
+
+/// Unset optional settings are printed commented out.
+pub fn print_config(path: &std::path::Path, entries: &[(String, Option<toml::Value>)]) {
+    println!("# {}", path.display());
+    for (key, value) in entries {
+        match value {
+            Some(value) => println!("{} = {}", key, value),
+            None => println!("# {} =", key),
+        }
+    }
+}
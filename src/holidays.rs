@@ -0,0 +1,241 @@
+// Public holidays, either from the built-in lists below or from an iCalendar
+// file. Both produce (day, name) pairs that get stored as absences.
+
+use anyhow::Result;
+use chrono::{Datelike, Months, NaiveDate, Weekday};
+
+pub const COUNTRIES: &[&str] = &["AT", "CH", "DE", "US"];
+
+/// Public holidays of a country (and optionally one of its regions) in a year.
+pub fn builtin(country: &str, region: Option<&str>, year: i32) -> Result<Vec<(NaiveDate, String)>> {
+    let country = country.to_uppercase();
+    let region = region.map(|r| r.to_uppercase());
+    let in_region = |regions: &[&str]| region.as_deref().is_some_and(|r| regions.contains(&r));
+
+    let date = |month: u32, day: u32| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+    let easter = easter_sunday(year);
+    let from_easter = |days: i64| easter + chrono::Duration::days(days);
+
+    let mut holidays: Vec<(NaiveDate, &str)> = Vec::new();
+    match country.as_str() {
+        "CH" => {
+            holidays.push((date(1, 1), "New Year's Day"));
+            if in_region(&["ZH", "BE", "LU", "OW", "GL", "ZG", "FR", "SO", "SH", "TG", "VD", "NE", "GE", "JU", "AG"]) {
+                holidays.push((date(1, 2), "Berchtold's Day"));
+            }
+            holidays.push((from_easter(-2), "Good Friday"));
+            holidays.push((from_easter(1), "Easter Monday"));
+            if in_region(&["ZH", "BS", "BL", "SH", "AG", "TG", "NE", "JU"]) {
+                holidays.push((date(5, 1), "Labour Day"));
+            }
+            holidays.push((from_easter(39), "Ascension Day"));
+            holidays.push((from_easter(50), "Whit Monday"));
+            if in_region(&["LU", "UR", "SZ", "OW", "NW", "ZG", "FR", "SO", "AI", "AG", "TI", "VS", "JU"]) {
+                holidays.push((from_easter(60), "Corpus Christi"));
+            }
+            holidays.push((date(8, 1), "Swiss National Day"));
+            holidays.push((date(12, 25), "Christmas Day"));
+            holidays.push((date(12, 26), "St. Stephen's Day"));
+        }
+        "DE" => {
+            holidays.push((date(1, 1), "New Year's Day"));
+            if in_region(&["BW", "BY", "ST"]) {
+                holidays.push((date(1, 6), "Epiphany"));
+            }
+            if in_region(&["BE", "MV"]) {
+                holidays.push((date(3, 8), "International Women's Day"));
+            }
+            holidays.push((from_easter(-2), "Good Friday"));
+            holidays.push((from_easter(1), "Easter Monday"));
+            holidays.push((date(5, 1), "Labour Day"));
+            holidays.push((from_easter(39), "Ascension Day"));
+            holidays.push((from_easter(50), "Whit Monday"));
+            if in_region(&["BW", "BY", "HE", "NW", "RP", "SL"]) {
+                holidays.push((from_easter(60), "Corpus Christi"));
+            }
+            if in_region(&["SL"]) {
+                holidays.push((date(8, 15), "Assumption Day"));
+            }
+            holidays.push((date(10, 3), "German Unity Day"));
+            if in_region(&["BB", "HB", "HH", "MV", "NI", "SN", "ST", "SH", "TH"]) {
+                holidays.push((date(10, 31), "Reformation Day"));
+            }
+            if in_region(&["BW", "BY", "NW", "RP", "SL"]) {
+                holidays.push((date(11, 1), "All Saints' Day"));
+            }
+            if in_region(&["SN"]) {
+                // Wednesday before 23 November
+                let mut day = date(11, 22);
+                while day.weekday() != Weekday::Wed {
+                    day = day.pred_opt().unwrap();
+                }
+                holidays.push((day, "Repentance and Prayer Day"));
+            }
+            holidays.push((date(12, 25), "Christmas Day"));
+            holidays.push((date(12, 26), "St. Stephen's Day"));
+        }
+        "AT" => {
+            holidays.push((date(1, 1), "New Year's Day"));
+            holidays.push((date(1, 6), "Epiphany"));
+            holidays.push((from_easter(1), "Easter Monday"));
+            holidays.push((date(5, 1), "Labour Day"));
+            holidays.push((from_easter(39), "Ascension Day"));
+            holidays.push((from_easter(50), "Whit Monday"));
+            holidays.push((from_easter(60), "Corpus Christi"));
+            holidays.push((date(8, 15), "Assumption Day"));
+            holidays.push((date(10, 26), "National Day"));
+            holidays.push((date(11, 1), "All Saints' Day"));
+            holidays.push((date(12, 8), "Immaculate Conception"));
+            holidays.push((date(12, 25), "Christmas Day"));
+            holidays.push((date(12, 26), "St. Stephen's Day"));
+        }
+        "US" => {
+            holidays.push((date(1, 1), "New Year's Day"));
+            holidays.push((nth_weekday(year, 1, Weekday::Mon, 3), "Martin Luther King Jr. Day"));
+            holidays.push((nth_weekday(year, 2, Weekday::Mon, 3), "Presidents' Day"));
+            holidays.push((last_weekday(year, 5, Weekday::Mon), "Memorial Day"));
+            holidays.push((date(6, 19), "Juneteenth"));
+            holidays.push((date(7, 4), "Independence Day"));
+            holidays.push((nth_weekday(year, 9, Weekday::Mon, 1), "Labor Day"));
+            holidays.push((nth_weekday(year, 10, Weekday::Mon, 2), "Columbus Day"));
+            holidays.push((date(11, 11), "Veterans Day"));
+            holidays.push((nth_weekday(year, 11, Weekday::Thu, 4), "Thanksgiving Day"));
+            holidays.push((date(12, 25), "Christmas Day"));
+        }
+        _ => anyhow::bail!("No built-in holidays for '{}'. Available: {}", country, COUNTRIES.join(", ")),
+    }
+
+    holidays.sort_by_key(|(day, _)| *day);
+    Ok(holidays.into_iter().map(|(day, name)| (day, name.to_string())).collect())
+}
+
+/// Easter Sunday in the Gregorian calendar (anonymous Gregorian algorithm).
+fn easter_sunday(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).unwrap()
+}
+
+fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u8) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, weekday, n).unwrap()
+}
+
+fn last_weekday(year: i32, month: u32, weekday: Weekday) -> NaiveDate {
+    let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+    let mut day = (first + Months::new(1)).pred_opt().unwrap();
+    while day.weekday() != weekday {
+        day = day.pred_opt().unwrap();
+    }
+    day
+}
+
+/// Reads all-day events from an iCalendar file. Multi-day events yield one
+/// entry per day; events with a time of day are skipped.
+pub fn parse_ics(content: &str) -> Vec<(NaiveDate, String)> {
+    // Long lines are folded by starting the continuation with a space or tab
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match line.strip_prefix([' ', '\t']) {
+            Some(rest) if !lines.is_empty() => lines.last_mut().unwrap().push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut days = Vec::new();
+    let (mut start, mut end, mut summary) = (None, None, None);
+    let mut in_event = false;
+
+    for line in &lines {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let name = key.split(';').next().unwrap_or("");
+
+        match name {
+            "BEGIN" if value == "VEVENT" => {
+                in_event = true;
+                (start, end, summary) = (None, None, None);
+            }
+            "DTSTART" if in_event => start = parse_ics_date(value),
+            "DTEND" if in_event => end = parse_ics_date(value),
+            "SUMMARY" if in_event => summary = Some(unescape_ics(value)),
+            "END" if value == "VEVENT" => {
+                in_event = false;
+                let Some(first) = start else {
+                    continue;
+                };
+                let last = end.and_then(|e| e.pred_opt()).filter(|l| *l >= first).unwrap_or(first);
+                let name = summary.clone().unwrap_or_else(|| "Holiday".to_string());
+                for day in days_between(first, last) {
+                    days.push((day, name.clone()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    days
+}
+
+/// Only plain dates (`YYYYMMDD`) count, date-times are not whole days off.
+fn parse_ics_date(value: &str) -> Option<NaiveDate> {
+    if value.len() != 8 {
+        return None;
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d").ok()
+}
+
+fn unescape_ics(value: &str) -> String {
+    value.replace("\\,", ",").replace("\\;", ";").replace("\\n", " ").replace("\\\\", "\\")
+}
+
+/// Days from `first` up to and including `last`.
+pub fn days_between(first: NaiveDate, last: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+    first.iter_days().take_while(move |d| *d <= last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn computes_easter_sunday() {
+        assert_eq!(easter_sunday(2000), ymd(2000, 4, 23));
+        assert_eq!(easter_sunday(2019), ymd(2019, 4, 21));
+        assert_eq!(easter_sunday(2024), ymd(2024, 3, 31));
+        assert_eq!(easter_sunday(2025), ymd(2025, 4, 20));
+        assert_eq!(easter_sunday(2026), ymd(2026, 4, 5));
+        // The earliest and latest possible dates
+        assert_eq!(easter_sunday(1818), ymd(1818, 3, 22));
+        assert_eq!(easter_sunday(2038), ymd(2038, 4, 25));
+    }
+
+    #[test]
+    fn moves_holidays_with_easter() {
+        let holidays = builtin("de", None, 2026).unwrap();
+        assert!(holidays.contains(&(ymd(2026, 4, 3), "Good Friday".to_string())));
+        assert!(holidays.contains(&(ymd(2026, 4, 6), "Easter Monday".to_string())));
+        assert!(holidays.contains(&(ymd(2026, 5, 14), "Ascension Day".to_string())));
+        assert!(holidays.contains(&(ymd(2026, 5, 25), "Whit Monday".to_string())));
+        assert!(!holidays.iter().any(|(_, name)| name == "Corpus Christi"));
+
+        let bavaria = builtin("DE", Some("by"), 2026).unwrap();
+        assert!(bavaria.contains(&(ymd(2026, 6, 4), "Corpus Christi".to_string())));
+    }
+}
@@ -0,0 +1,360 @@
+use rusqlite::Connection;
+use anyhow::Result;
+use chrono::{Local, NaiveDate};
+use crate::billing::{self, Client, Pricing};
+use crate::rounding::Rounding;
+use crate::{queries, settings};
+
+pub enum Grouping {
+    Topic,
+    Day,
+}
+
+pub struct InvoiceLine {
+    pub description: String,
+    pub hours: f64,
+    /// None if the line combines sessions with different rates
+    pub rate: Option<f64>,
+    pub amount: f64,
+}
+
+pub struct Invoice {
+    pub number: String,
+    pub client: String,
+    pub currency: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub issued: NaiveDate,
+    pub lines: Vec<InvoiceLine>,
+    /// Percent, e.g. 8.1
+    pub tax_rate: f64,
+    pub session_ids: Vec<i64>,
+    /// Tracked hours before rounding
+    pub raw_hours: f64,
+    pub rounding: Rounding,
+}
+
+impl Invoice {
+    pub fn hours(&self) -> f64 {
+        self.lines.iter().map(|l| l.hours).sum()
+    }
+
+    pub fn subtotal(&self) -> f64 {
+        self.lines.iter().map(|l| l.amount).sum()
+    }
+
+    pub fn tax(&self) -> f64 {
+        self.subtotal() * self.tax_rate / 100.0
+    }
+
+    pub fn total(&self) -> f64 {
+        self.subtotal() + self.tax()
+    }
+}
+
+pub struct InvoiceRecord {
+    pub number: String,
+    pub client: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub issued: NaiveDate,
+    pub currency: String,
+    pub hours: f64,
+    pub total: f64,
+    pub sessions: usize,
+}
+
+/// Collects the client's billable, not yet invoiced sessions started between
+/// `from` and `to` (inclusive) into invoice lines.
+pub fn build(
+    conn: &Connection,
+    pricing: &Pricing,
+    client: &Client,
+    from: NaiveDate,
+    to: NaiveDate,
+    grouping: Grouping,
+    tax_rate: f64,
+) -> Result<Invoice> {
+    let filter = queries::SessionFilter {
+        from: Some(queries::to_rfc3339(from.and_hms_opt(0, 0, 0).unwrap())?),
+        to: Some(queries::to_rfc3339(to.succ_opt().unwrap().and_hms_opt(0, 0, 0).unwrap())?),
+        uninvoiced: true,
+        ..Default::default()
+    };
+
+    let rounding = pricing.rounding_for(client.id).clone();
+    // One entry per session, keyed by line and rate so rounding never mixes rates
+    let mut entries: Vec<((String, u64), NaiveDate, f64)> = Vec::new();
+    let mut session_ids = Vec::new();
+
+    for (session, hours) in queries::get_matching_sessions(conn, &filter)? {
+        if session.end.is_none() {
+            continue;
+        }
+        let Some(project) = pricing.project_for(&session.topic) else {
+            continue;
+        };
+        if project.client_id != client.id || !project.billable {
+            continue;
+        }
+
+        let date = session.start.date_naive();
+        let rate = pricing.rate_for(project, &session.topic, date).ok_or_else(|| anyhow::anyhow!(
+            "No rate for '{}' on {}. Set one with 'walrus rate set'",
+            session.topic, settings::date(date)
+        ))?;
+
+        let description = match grouping {
+            Grouping::Topic => session.topic.clone(),
+            Grouping::Day => settings::date(date),
+        };
+
+        entries.push(((description, rate.to_bits()), date, hours));
+        session_ids.push(session.id);
+    }
+
+    let mut lines: Vec<InvoiceLine> = Vec::new();
+    for (((description, rate), _, _), hours) in entries.iter().zip(rounding.apply(&entries)) {
+        let rate = f64::from_bits(*rate);
+        match lines.iter_mut().find(|l| l.description == *description) {
+            Some(line) => {
+                line.hours += hours;
+                line.amount += hours * rate;
+                if line.rate != Some(rate) {
+                    line.rate = None;
+                }
+            }
+            None => lines.push(InvoiceLine { description: description.clone(), hours, rate: Some(rate), amount: hours * rate }),
+        }
+    }
+
+    if lines.is_empty() {
+        anyhow::bail!(
+            "No billable, uninvoiced sessions for {} between {} and {}",
+            client.name, settings::date(from), settings::date(to)
+        );
+    }
+
+    if let Grouping::Topic = grouping {
+        lines.sort_by(|a, b| a.description.cmp(&b.description));
+    }
+
+    let issued = Local::now().date_naive();
+    Ok(Invoice {
+        number: next_number(conn, issued)?,
+        client: client.name.clone(),
+        currency: client.currency.clone(),
+        from,
+        to,
+        issued,
+        lines,
+        tax_rate,
+        session_ids,
+        raw_hours: entries.iter().map(|(_, _, hours)| hours).sum(),
+        rounding,
+    })
+}
+
+/// Invoice numbers count up per year: 2025-001, 2025-002, ...
+fn next_number(conn: &Connection, issued: NaiveDate) -> Result<String> {
+    let year = issued.format("%Y").to_string();
+    let last: Option<i64> = conn.query_row(
+        "SELECT MAX(CAST(substr(number, length(?1) + 2) AS INTEGER)) FROM invoices WHERE number LIKE ?1 || '-%'",
+        [&year],
+        |row| row.get(0),
+    )?;
+    Ok(format!("{}-{:03}", year, last.unwrap_or(0) + 1))
+}
+
+/// Stores the invoice and marks its sessions as invoiced and locked.
+pub fn save(conn: &Connection, client: &Client, invoice: &Invoice) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+
+    tx.execute(
+        "INSERT INTO invoices (number, client_id, period_from, period_to, issued, currency,
+                               hours, subtotal, tax_rate, total)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        rusqlite::params![
+            invoice.number,
+            client.id,
+            invoice.from.format("%Y-%m-%d").to_string(),
+            invoice.to.format("%Y-%m-%d").to_string(),
+            invoice.issued.format("%Y-%m-%d").to_string(),
+            invoice.currency,
+            invoice.hours(),
+            invoice.subtotal(),
+            invoice.tax_rate,
+            invoice.total(),
+        ],
+    )?;
+    let invoice_id = tx.last_insert_rowid();
+
+    for id in &invoice.session_ids {
+        tx.execute("UPDATE sessions SET invoice_id = ?1, locked = 1 WHERE id = ?2", [invoice_id, *id])?;
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn get_invoices(conn: &Connection) -> Result<Vec<InvoiceRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT i.number, c.name, i.period_from, i.period_to, i.issued, i.currency, i.hours, i.total,
+                (SELECT COUNT(*) FROM sessions s WHERE s.invoice_id = i.id)
+         FROM invoices i
+         JOIN clients c ON c.id = i.client_id
+         ORDER BY i.issued DESC, i.number DESC"
+    )?;
+
+    let invoices = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, String>(4)?,
+            row.get::<_, String>(5)?,
+            row.get::<_, f64>(6)?,
+            row.get::<_, f64>(7)?,
+            row.get::<_, i64>(8)?,
+        ))
+    })?;
+
+    let parse = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d");
+    let mut result = Vec::new();
+    for invoice in invoices {
+        let (number, client, from, to, issued, currency, hours, total, sessions) = invoice?;
+        result.push(InvoiceRecord {
+            number,
+            client,
+            from: parse(&from)?,
+            to: parse(&to)?,
+            issued: parse(&issued)?,
+            currency,
+            hours,
+            total,
+            sessions: sessions as usize,
+        });
+    }
+
+    Ok(result)
+}
+
+fn rate_text(invoice: &Invoice, line: &InvoiceLine) -> String {
+    line.rate
+        .map(|r| billing::format_money(r, &invoice.currency))
+        .unwrap_or_else(|| "mixed".to_string())
+}
+
+pub fn to_markdown(invoice: &Invoice) -> String {
+    let money = |amount: f64| billing::format_money(amount, &invoice.currency);
+    let mut md = String::new();
+
+    md.push_str(&format!("# Invoice {}\n\n", invoice.number));
+    md.push_str(&format!("**Client:** {}  \n", invoice.client));
+    md.push_str(&format!("**Issued:** {}  \n", settings::date(invoice.issued)));
+    md.push_str(&format!(
+        "**Period:** {} – {}\n\n",
+        settings::date(invoice.from),
+        settings::date(invoice.to)
+    ));
+
+    md.push_str("| Description | Hours | Rate | Amount |\n");
+    md.push_str("|---|---:|---:|---:|\n");
+    for line in &invoice.lines {
+        md.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            line.description.replace('|', "\\|"),
+            settings::hours(line.hours),
+            rate_text(invoice, line),
+            money(line.amount)
+        ));
+    }
+
+    md.push_str(&format!("| **Subtotal** | {} | | {} |\n", settings::hours(invoice.hours()), money(invoice.subtotal())));
+    if invoice.tax_rate > 0.0 {
+        md.push_str(&format!("| Tax ({}%) | | | {} |\n", invoice.tax_rate, money(invoice.tax())));
+    }
+    md.push_str(&format!("| **Total** | | | **{}** |\n", money(invoice.total())));
+
+    md
+}
+
+pub fn to_html(invoice: &Invoice) -> String {
+    let money = |amount: f64| billing::format_money(amount, &invoice.currency);
+    let mut rows = String::new();
+
+    for line in &invoice.lines {
+        rows.push_str(&format!(
+            "      <tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td></tr>\n",
+            escape_html(&line.description),
+            settings::hours(line.hours),
+            escape_html(&rate_text(invoice, line)),
+            escape_html(&money(line.amount))
+        ));
+    }
+
+    let tax_row = if invoice.tax_rate > 0.0 {
+        format!(
+            "      <tr><td>Tax ({}%)</td><td></td><td></td><td class=\"num\">{}</td></tr>\n",
+            invoice.tax_rate,
+            escape_html(&money(invoice.tax()))
+        )
+    } else {
+        String::new()
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>Invoice {number}</title>
+  <style>
+    body {{ font-family: sans-serif; max-width: 800px; margin: 2em auto; color: #222; }}
+    table {{ width: 100%; border-collapse: collapse; margin-top: 2em; }}
+    th, td {{ padding: 0.4em 0.6em; border-bottom: 1px solid #ddd; text-align: left; }}
+    .num {{ text-align: right; }}
+    tfoot td {{ font-weight: bold; border-bottom: none; }}
+  </style>
+</head>
+<body>
+  <h1>Invoice {number}</h1>
+  <p>
+    <strong>Client:</strong> {client}<br>
+    <strong>Issued:</strong> {issued}<br>
+    <strong>Period:</strong> {from} – {to}
+  </p>
+  <table>
+    <thead>
+      <tr><th>Description</th><th class="num">Hours</th><th class="num">Rate</th><th class="num">Amount</th></tr>
+    </thead>
+    <tbody>
+{rows}    </tbody>
+    <tfoot>
+      <tr><td>Subtotal</td><td class="num">{hours}</td><td></td><td class="num">{subtotal}</td></tr>
+{tax_row}      <tr><td>Total</td><td></td><td></td><td class="num">{total}</td></tr>
+    </tfoot>
+  </table>
+</body>
+</html>
+"#,
+        number = escape_html(&invoice.number),
+        client = escape_html(&invoice.client),
+        issued = escape_html(&settings::date(invoice.issued)),
+        from = escape_html(&settings::date(invoice.from)),
+        to = escape_html(&settings::date(invoice.to)),
+        rows = rows,
+        hours = settings::hours(invoice.hours()),
+        subtotal = escape_html(&money(invoice.subtotal())),
+        tax_row = tax_row,
+        total = escape_html(&money(invoice.total())),
+    )
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
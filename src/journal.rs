@@ -0,0 +1,279 @@
+use rusqlite::{Connection, OptionalExtension};
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Local};
+use crate::queries;
+
+// Every change to a session is journaled as a pair of row images (JSON objects
+// built by SQLite). Undo writes the `before` image back, redo the `after` image.
+// Keep these two in sync with the columns of the sessions table. Bookkeeping
+// columns like `invoice_id` and `locked` are deliberately left out so undo never
+// touches them, which is why invoiced sessions are never removed for good.
+const SESSION_IMAGE: &str =
+    "json_object('id', id, 'topic', topic, 'start_time', start_time, 'end_time', end_time,
+                 'deleted_at', deleted_at, 'pomodoro', pomodoro)";
+
+const RESTORE_SESSION: &str =
+    "INSERT INTO sessions (id, topic, start_time, end_time, deleted_at, pomodoro)
+     SELECT json_extract(?1, '$.id'), json_extract(?1, '$.topic'),
+            json_extract(?1, '$.start_time'), json_extract(?1, '$.end_time'),
+            json_extract(?1, '$.deleted_at'), json_extract(?1, '$.pomodoro')
+     WHERE true
+     ON CONFLICT(id) DO UPDATE SET
+         topic = excluded.topic, start_time = excluded.start_time,
+         end_time = excluded.end_time, deleted_at = excluded.deleted_at,
+         pomodoro = excluded.pomodoro";
+
+pub struct Operation {
+    pub id: i64,
+    pub description: String,
+    pub created: DateTime<FixedOffset>,
+    pub undone: bool,
+    pub changes: usize,
+}
+
+/// Runs `f` inside a transaction and records every session change it makes as
+/// one undoable operation. Operations that change nothing are not kept.
+pub fn transaction<T>(conn: &Connection, description: &str, f: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+    let tx = conn.unchecked_transaction()?;
+
+    tx.execute(
+        "INSERT INTO operations (description, created_at, status) VALUES (?1, ?2, 'open')",
+        [description, &Local::now().to_rfc3339()],
+    )?;
+    let op_id = tx.last_insert_rowid();
+
+    let result = f(&tx)?;
+
+    let changes: i64 = tx.query_row("SELECT COUNT(*) FROM journal WHERE op_id = ?1", [op_id], |row| row.get(0))?;
+    if changes == 0 {
+        tx.execute("DELETE FROM operations WHERE id = ?1", [op_id])?;
+    } else {
+        // A new change invalidates everything that could have been redone
+        tx.execute("DELETE FROM journal WHERE op_id IN (SELECT id FROM operations WHERE status = 'undone')", [])?;
+        tx.execute("DELETE FROM operations WHERE status = 'undone'", [])?;
+        tx.execute("UPDATE operations SET status = 'done' WHERE id = ?1", [op_id])?;
+    }
+
+    tx.commit()?;
+    Ok(result)
+}
+
+pub fn session_image(conn: &Connection, id: i64) -> Result<Option<String>> {
+    let image = conn.query_row(
+        &format!("SELECT {} FROM sessions WHERE id = ?1", SESSION_IMAGE),
+        [id],
+        |row| row.get(0),
+    ).optional()?;
+    Ok(image)
+}
+
+pub fn record_change(conn: &Connection, session_id: i64, before: Option<String>, after: Option<String>) -> Result<()> {
+    if before == after {
+        return Ok(());
+    }
+
+    let op_id: i64 = conn.query_row(
+        "SELECT id FROM operations WHERE status = 'open'",
+        [],
+        |row| row.get(0),
+    ).optional()?
+        .ok_or_else(|| anyhow::anyhow!("Session changes must run inside a journal transaction"))?;
+
+    conn.execute(
+        "INSERT INTO journal (op_id, session_id, before, after) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![op_id, session_id, before, after],
+    )?;
+    Ok(())
+}
+
+/// Reverts the most recent operation. Returns its description, or None if
+/// there is nothing left to undo.
+pub fn undo(conn: &Connection) -> Result<Option<String>> {
+    let op = conn.query_row(
+        "SELECT id, description FROM operations WHERE status = 'done' ORDER BY id DESC LIMIT 1",
+        [],
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+    ).optional()?;
+
+    let Some((op_id, description)) = op else {
+        return Ok(None);
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    replay(&tx, op_id, Direction::Backward)?;
+    tx.execute("UPDATE operations SET status = 'undone' WHERE id = ?1", [op_id])?;
+    tx.commit()?;
+
+    Ok(Some(description))
+}
+
+/// Re-applies the most recently undone operation. Returns its description, or
+/// None if there is nothing to redo.
+pub fn redo(conn: &Connection) -> Result<Option<String>> {
+    let op = conn.query_row(
+        "SELECT id, description FROM operations WHERE status = 'undone' ORDER BY id ASC LIMIT 1",
+        [],
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+    ).optional()?;
+
+    let Some((op_id, description)) = op else {
+        return Ok(None);
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    replay(&tx, op_id, Direction::Forward)?;
+    tx.execute("UPDATE operations SET status = 'done' WHERE id = ?1", [op_id])?;
+    tx.commit()?;
+
+    Ok(Some(description))
+}
+
+pub fn history(conn: &Connection, limit: usize) -> Result<Vec<Operation>> {
+    let mut stmt = conn.prepare(
+        "SELECT o.id, o.description, o.created_at, o.status,
+                (SELECT COUNT(*) FROM journal j WHERE j.op_id = o.id)
+         FROM operations o
+         WHERE o.status != 'open'
+         ORDER BY o.id DESC
+         LIMIT ?1"
+    )?;
+
+    let ops = stmt.query_map([limit], |row| {
+        let id: i64 = row.get(0)?;
+        let description: String = row.get(1)?;
+        let created_str: String = row.get(2)?;
+        let status: String = row.get(3)?;
+        let changes: i64 = row.get(4)?;
+        Ok((id, description, created_str, status, changes))
+    })?;
+
+    let mut result = Vec::new();
+    for op in ops {
+        let (id, description, created_str, status, changes) = op?;
+        let created = DateTime::parse_from_rfc3339(&created_str)?;
+        result.push(Operation {
+            id,
+            description,
+            created,
+            undone: status == "undone",
+            changes: changes as usize,
+        });
+    }
+
+    Ok(result)
+}
+
+enum Direction {
+    Backward,
+    Forward,
+}
+
+fn replay(conn: &Connection, op_id: i64, direction: Direction) -> Result<()> {
+    let order = match direction {
+        Direction::Backward => "DESC",
+        Direction::Forward => "ASC",
+    };
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT session_id, before, after FROM journal WHERE op_id = ?1 ORDER BY id {}",
+        order
+    ))?;
+    let entries = stmt.query_map([op_id], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, Option<String>>(2)?))
+    })?.collect::<Result<Vec<_>, _>>()?;
+
+    for (session_id, before, after) in entries {
+        let (expected, target) = match direction {
+            Direction::Backward => (after, before),
+            Direction::Forward => (before, after),
+        };
+
+        if session_image(conn, session_id)? != expected {
+            anyhow::bail!("Session {} was changed outside of the history, cannot replay", session_id);
+        }
+
+        // Locked sessions can't be changed by replaying history either, neither
+        // in their current state nor in the one being restored
+        queries::ensure_unlocked(conn, session_id)?;
+        match target {
+            Some(image) => conn.execute(RESTORE_SESSION, [&image])?,
+            None => {
+                queries::ensure_not_invoiced(conn, session_id)?;
+                conn.execute("DELETE FROM sessions WHERE id = ?1", [session_id])?
+            }
+        };
+        queries::ensure_unlocked(conn, session_id)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    #[test]
+    fn undo_and_redo_restore_the_row_images() {
+        let conn = db::open_in_memory();
+        let id = transaction(&conn, "start acme", |tx| queries::start_session(tx, "acme")).unwrap();
+        transaction(&conn, "stop acme", |tx| {
+            queries::stop_session(tx, id)?;
+            queries::mark_pomodoro(tx, id)
+        }).unwrap();
+        let stopped = session_image(&conn, id).unwrap();
+        assert!(stopped.as_deref().unwrap().contains(r#""pomodoro":1"#));
+
+        transaction(&conn, "delete session", |tx| queries::delete_session(tx, id)).unwrap();
+        let trashed = session_image(&conn, id).unwrap();
+        transaction(&conn, "empty trash", |tx| queries::empty_trash(tx, None)).unwrap();
+        assert_eq!(session_image(&conn, id).unwrap(), None);
+
+        assert_eq!(undo(&conn).unwrap().as_deref(), Some("empty trash"));
+        assert_eq!(session_image(&conn, id).unwrap(), trashed);
+        assert_eq!(undo(&conn).unwrap().as_deref(), Some("delete session"));
+        assert_eq!(session_image(&conn, id).unwrap(), stopped);
+
+        assert_eq!(redo(&conn).unwrap().as_deref(), Some("delete session"));
+        assert_eq!(session_image(&conn, id).unwrap(), trashed);
+        assert_eq!(redo(&conn).unwrap().as_deref(), Some("empty trash"));
+        assert_eq!(session_image(&conn, id).unwrap(), None);
+        assert_eq!(redo(&conn).unwrap(), None);
+    }
+
+    #[test]
+    fn undoing_an_add_removes_the_row() {
+        let conn = db::open_in_memory();
+        let id = transaction(&conn, "start acme", |tx| queries::start_session(tx, "acme")).unwrap();
+
+        assert_eq!(undo(&conn).unwrap().as_deref(), Some("start acme"));
+        assert_eq!(session_image(&conn, id).unwrap(), None);
+        assert_eq!(undo(&conn).unwrap(), None);
+    }
+
+    #[test]
+    fn replay_refuses_rows_changed_outside_the_history() {
+        let conn = db::open_in_memory();
+        let id = transaction(&conn, "start acme", |tx| queries::start_session(tx, "acme")).unwrap();
+        conn.execute("UPDATE sessions SET topic = 'other' WHERE id = ?1", [id]).unwrap();
+
+        assert!(undo(&conn).is_err());
+        assert!(session_image(&conn, id).unwrap().is_some());
+    }
+
+    #[test]
+    fn invoiced_sessions_are_not_removed_by_undo() {
+        let conn = db::open_in_memory();
+        let id = transaction(&conn, "start acme", |tx| queries::start_session(tx, "acme")).unwrap();
+        conn.execute_batch(
+            "INSERT INTO clients (id, name, currency) VALUES (1, 'Acme', 'EUR');
+             INSERT INTO invoices (id, number, client_id, period_from, period_to, issued, currency,
+                                   hours, subtotal, tax_rate, total)
+                 VALUES (1, '2026-001', 1, '2026-10-01', '2026-10-31', '2026-11-01', 'EUR', 1, 100, 0, 100);
+             UPDATE sessions SET invoice_id = 1",
+        ).unwrap();
+
+        assert!(undo(&conn).is_err());
+        assert!(session_image(&conn, id).unwrap().is_some());
+    }
+}
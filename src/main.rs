@@ -2,9 +2,29 @@ mod db;
 mod commands;
 mod queries;
 mod display;
+mod journal;
+mod backup;
+mod config;
+mod topics;
+mod billing;
+mod invoice;
+mod targets;
+mod periods;
+mod schedule;
+mod absences;
+mod holidays;
+mod rounding;
+mod stale;
+mod pomodoro;
+mod tui;
+mod complete;
+mod settings;
+mod timesheet;
 
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::{ArgValueCompleter, CompleteEnv, Shell};
 use anyhow::Result;
+use rounding::{RoundingMode, RoundingScope};
 
 #[derive(Parser)]
 #[command(name = "walrus")]
@@ -12,31 +32,371 @@ use anyhow::Result;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Color output, overrides display.color from the config
+    #[arg(long, global = true, value_name = "WHEN")]
+    color: Option<settings::ColorChoice>,
 }
 
-#[derive(Clone, ValueEnum)]
+/// Years accepted by `--year`. The built-in holidays follow the Gregorian
+/// calendar, which starts in 1583.
+const YEARS: std::ops::RangeInclusive<i64> = 1583..=9999;
+
+/// Rates and tax: a finite number, zero or more.
+fn parse_amount(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(amount) if amount.is_finite() && amount >= 0.0 => Ok(amount),
+        _ => Err("use a number of 0 or more".to_string()),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
 pub enum Period {
     Day,
     Week,
     Month,
+    /// Quarter of the (fiscal) year
+    Quarter,
+    /// Calendar year, or fiscal year if calendar.fiscal_year_start is set
     Year,
 }
 
+#[derive(Args)]
+pub struct ShowArgs {
+    #[arg(short = 'n', long, default_value = "1")]
+    pub count: usize,
+    #[arg(short = 'p', long)]
+    pub period: Option<Period>,
+    /// Start at the period this many periods back, e.g. -p week --offset 1 for last week
+    #[arg(long, default_value = "0", requires = "period", conflicts_with = "date")]
+    pub offset: usize,
+    /// The day, or with -p the period, containing this date
+    #[arg(long, value_name = "DATE", conflicts_with = "count")]
+    pub date: Option<String>,
+    /// First day of a range, shown day by day
+    #[arg(long, value_name = "DATE", conflicts_with_all = ["period", "date", "count"])]
+    pub from: Option<String>,
+    /// Last day of the range (default: today)
+    #[arg(long, value_name = "DATE", requires = "from")]
+    pub to: Option<String>,
+    #[command(flatten)]
+    pub topics: TopicArgs,
+    /// Collapse topic levels below this depth
+    #[arg(long)]
+    pub depth: Option<usize>,
+    /// Draw a bar per topic, and a sparkline of the totals over several periods
+    #[arg(long)]
+    pub chart: bool,
+}
+
+// Limits reports and lists to some topics. A plain comment, since clap would
+// take a doc comment as the about text of the commands flattening it.
+#[derive(Args)]
+pub struct TopicArgs {
+    /// Only topics matching this pattern, e.g. acme/** (repeatable)
+    #[arg(long, add = ArgValueCompleter::new(complete::topics))]
+    pub topic: Vec<String>,
+    /// Leave out topics matching this pattern (repeatable)
+    #[arg(long, add = ArgValueCompleter::new(complete::topics))]
+    pub exclude_topic: Vec<String>,
+}
+
+/// Selects sessions by topic and start time
+#[derive(Args)]
+pub struct FilterArgs {
+    /// Only sessions whose topic matches this pattern (repeatable)
+    #[arg(long, add = ArgValueCompleter::new(complete::topics))]
+    pub topic: Vec<String>,
+    /// Only sessions started before this date
+    #[arg(long, value_name = "DD.MM.YYYY [HH:MM]")]
+    pub before: Option<String>,
+    /// Only sessions started on or after this date
+    #[arg(long, value_name = "DD.MM.YYYY [HH:MM]")]
+    pub from: Option<String>,
+    /// Only sessions started up to this date (inclusive for plain dates)
+    #[arg(long, value_name = "DD.MM.YYYY [HH:MM]")]
+    pub to: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the value in effect for a setting, e.g. display.date_format
+    Get { key: String },
+    /// Write a setting to the config file
+    Set { key: String, value: String },
+    /// Remove a setting from the config file so its default applies
+    Unset { key: String },
+    /// Print every setting with the value in effect
+    List,
+}
+
+#[derive(Subcommand)]
+enum TopicAction {
+    /// Rename a topic across all sessions (case-insensitive)
+    Rename {
+        #[arg(add = ArgValueCompleter::new(complete::topics))]
+        old: String,
+        new: String,
+        /// Don't ask for confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+    /// Merge several topics into one (case-insensitive)
+    Merge {
+        #[arg(required = true, add = ArgValueCompleter::new(complete::topics))]
+        topics: Vec<String>,
+        #[arg(long)]
+        into: String,
+        /// Don't ask for confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ClientAction {
+    /// Add a client
+    Add {
+        name: String,
+        #[arg(long, default_value = "CHF")]
+        currency: String,
+        /// Default hourly rate for all of the client's projects
+        #[arg(long, value_parser = parse_amount)]
+        rate: Option<f64>,
+    },
+    /// List clients
+    List,
+    /// Show or set how the client's time is rounded for billing
+    Rounding {
+        name: String,
+        #[arg(long, value_enum)]
+        mode: Option<RoundingMode>,
+        /// Increment in minutes, 0 turns rounding off for the client
+        #[arg(long)]
+        increment: Option<u32>,
+        #[arg(long, value_enum)]
+        per: Option<RoundingScope>,
+        /// Go back to the configured default
+        #[arg(long, conflicts_with_all = ["mode", "increment", "per"])]
+        clear: bool,
+    },
+    /// Remove a client with all its projects and rates
+    Remove {
+        name: String,
+        /// Don't ask for confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProjectAction {
+    /// Add a project that claims all sessions matching a topic pattern
+    Add {
+        name: String,
+        #[arg(long)]
+        client: String,
+        /// Topic pattern, e.g. acme/website/**
+        #[arg(long)]
+        topic: String,
+        #[arg(long)]
+        non_billable: bool,
+        /// Hourly rate for this project
+        #[arg(long, value_parser = parse_amount)]
+        rate: Option<f64>,
+    },
+    /// Change a project's topic pattern or billable flag
+    Edit {
+        name: String,
+        #[arg(long)]
+        topic: Option<String>,
+        #[arg(long)]
+        billable: Option<bool>,
+    },
+    /// List projects
+    List,
+    /// Remove a project and its rates
+    Remove { name: String },
+}
+
+#[derive(Subcommand)]
+enum RateAction {
+    /// Set an hourly rate for a client, project or topic
+    Set {
+        #[arg(value_parser = parse_amount)]
+        rate: f64,
+        #[arg(long)]
+        client: Option<String>,
+        #[arg(long)]
+        project: Option<String>,
+        /// Topic pattern overriding the project rate
+        #[arg(long)]
+        topic: Option<String>,
+        /// Date from which the rate applies (default: always)
+        #[arg(long, value_name = "DD.MM.YYYY")]
+        from: Option<String>,
+    },
+    /// List rates
+    List,
+    /// Remove a rate
+    Remove { id: i64 },
+}
+
+#[derive(Subcommand)]
+enum TargetAction {
+    /// Set the hours expected per period, overall or for a topic pattern
+    Set {
+        hours: f64,
+        #[arg(short = 'p', long, default_value = "week")]
+        per: Period,
+        /// Topic pattern, e.g. acme/** (default: all tracked time)
+        #[arg(long)]
+        topic: Option<String>,
+    },
+    /// List targets
+    List,
+    /// Remove a target
+    Remove { id: i64 },
+}
+
+#[derive(Subcommand)]
+enum ScheduleAction {
+    /// Set the expected hours per weekday from a date on
+    Set {
+        /// One value for Monday to Friday, or seven values for Monday to Sunday
+        #[arg(required = true, num_args = 1..=7)]
+        hours: Vec<f64>,
+        /// First day the schedule applies (default: today)
+        #[arg(long, value_name = "DATE")]
+        from: Option<String>,
+    },
+    /// List schedules
+    List,
+    /// Remove a schedule
+    Remove { id: i64 },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OffKind {
+    Vacation,
+    Sick,
+    Holiday,
+    Other,
+}
+
+#[derive(Subcommand)]
+enum OffAction {
+    /// Record a day off, or every working day of a range with --to
+    Add {
+        #[arg(value_name = "DATE")]
+        date: String,
+        /// Last day of a range
+        #[arg(long, value_name = "DATE")]
+        to: Option<String>,
+        #[arg(long, default_value = "vacation")]
+        kind: OffKind,
+        /// Only half the day is off
+        #[arg(long)]
+        half: bool,
+        /// Description, e.g. the name of a holiday
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// List the days off of a year
+    List {
+        /// Default: current year
+        #[arg(long, value_parser = clap::value_parser!(i32).range(YEARS))]
+        year: Option<i32>,
+    },
+    /// Remove recorded days off
+    Remove {
+        #[arg(value_name = "DATE")]
+        date: String,
+        /// Last day of a range
+        #[arg(long, value_name = "DATE")]
+        to: Option<String>,
+    },
+    /// Import public holidays from an .ics file or the built-in lists
+    Import {
+        /// iCalendar file with all-day events
+        #[arg(required_unless_present = "country")]
+        file: Option<String>,
+        /// Built-in holidays of a country (AT, CH, DE, US)
+        #[arg(long, conflicts_with = "file")]
+        country: Option<String>,
+        /// Region for regional holidays, e.g. ZH or BY
+        #[arg(long, requires = "country")]
+        region: Option<String>,
+        /// Year of the built-in holidays (default: current year)
+        #[arg(long, requires = "country", value_parser = clap::value_parser!(i32).range(YEARS))]
+        year: Option<i32>,
+        #[arg(long, default_value = "holiday")]
+        kind: OffKind,
+    },
+}
+
+#[derive(Subcommand)]
+enum ReportKind {
+    /// Price tracked hours per client
+    Billing {
+        /// Month to report (default: current month)
+        #[arg(long, value_name = "YYYY-MM")]
+        month: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TrashAction {
+    /// List deleted sessions
+    List,
+    /// Move a session back out of the trash
+    Restore { id: i64 },
+    /// Permanently delete trashed sessions
+    Empty {
+        /// Only sessions deleted longer ago than this (e.g. 30d, 2w, 12h)
+        #[arg(long, value_name = "AGE")]
+        older_than: Option<String>,
+        /// Don't ask for confirmation when emptying the whole trash
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+}
+
+#[derive(Clone, ValueEnum)]
+pub enum InvoiceGrouping {
+    Topic,
+    Day,
+}
+
+#[derive(Clone, ValueEnum)]
+pub enum TimesheetFormat {
+    Terminal,
+    Csv,
+    Markdown,
+}
+
 #[derive(Subcommand)]
 enum Commands {
-    Start { topic: Option<String> },
-    Stop { topic: Option<String> },
+    Start {
+        #[arg(add = ArgValueCompleter::new(complete::topics))]
+        topic: Option<String>,
+    },
+    Stop {
+        #[arg(add = ArgValueCompleter::new(complete::active_topics))]
+        topic: Option<String>,
+    },
     Show {
-        #[arg(short = 'n', long, default_value = "1")]
-        count: usize,
-        #[arg(short = 'p', long)]
-        period: Option<Period>,
+        #[command(flatten)]
+        args: ShowArgs,
     },
     List {
-        #[arg(short = 'n', long, default_value = "10")]
-        count: usize,
+        /// Default: defaults.list_size from the config
+        #[arg(short = 'n', long)]
+        count: Option<usize>,
+        #[command(flatten)]
+        topics: TopicArgs,
     },
     Add {
+        #[arg(add = ArgValueCompleter::new(complete::topics))]
         topic: String,
         #[arg(short = 's', long, value_name = "DD.MM.YYYY HH:MM")]
         start: String,
@@ -44,36 +404,339 @@ enum Commands {
         end: String,
     },
     Edit {
+        #[arg(add = ArgValueCompleter::new(complete::session_ids))]
         id: i64,
-        #[arg(short = 't', long)]
+        #[arg(short = 't', long, add = ArgValueCompleter::new(complete::topics))]
         topic: Option<String>,
         #[arg(short = 's', long, value_name = "DD.MM.YYYY HH:MM")]
         start: Option<String>,
         #[arg(short = 'e', long, value_name = "DD.MM.YYYY HH:MM")]
         end: Option<String>,
     },
-    Delete { id: i64 },
+    /// Move sessions to the trash
+    Delete {
+        /// Session ids or ranges like 12-20
+        #[arg(add = ArgValueCompleter::new(complete::session_ids))]
+        ids: Vec<String>,
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Don't ask for confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
     Export,
-    Reset,
+    /// Print a shell completion script, e.g. `source <(walrus completions bash)`
+    Completions { shell: Shell },
+    /// Show running sessions, today's total and target progress
+    Status {
+        /// Refresh every second until interrupted
+        #[arg(short = 'w', long)]
+        watch: bool,
+    },
+    /// Print the running sessions for a shell prompt or status bar
+    Prompt {
+        /// Placeholders: {topic}, {elapsed}, {hours}, {start}
+        #[arg(short = 'f', long, default_value = "{topic} {elapsed}")]
+        format: String,
+        /// Goes between several running sessions
+        #[arg(long, default_value = " | ")]
+        separator: String,
+        /// JSON with text, tooltip and class for a waybar custom module
+        #[arg(long)]
+        waybar: bool,
+    },
+    /// Read and change settings in the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Full screen view to watch, start, stop and edit sessions
+    Tui,
+    /// Work in timed intervals with breaks in between, tracking each interval
+    Pomodoro {
+        #[arg(add = ArgValueCompleter::new(complete::topics))]
+        topic: String,
+        /// Length of a work interval
+        #[arg(long, default_value = "25m")]
+        work: String,
+        /// Length of a break
+        #[arg(long = "break", default_value = "5m")]
+        pause: String,
+        /// Number of work intervals
+        #[arg(long, default_value = "4")]
+        cycles: u32,
+        /// Also track breaks, as the topic 'break'
+        #[arg(long)]
+        record_breaks: bool,
+    },
+    /// Stop forgotten sessions without asking
+    FixStale {
+        /// Stop them at this time instead (HH:MM on the day they started, or DD.MM.YYYY HH:MM)
+        #[arg(long, conflicts_with = "last_activity")]
+        at: Option<String>,
+        /// Stop them when another session was last stopped, where there is one
+        #[arg(long)]
+        last_activity: bool,
+        /// Only show what would be changed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Permanently delete all sessions, or only the given ones or those matching the filters
+    Reset {
+        /// Session ids or ranges like 12-20
+        #[arg(add = ArgValueCompleter::new(complete::session_ids))]
+        ids: Vec<String>,
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// Don't ask for confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+    /// Rename or merge topics
+    Topic {
+        #[command(subcommand)]
+        action: TopicAction,
+    },
+    /// Manage clients
+    Client {
+        #[command(subcommand)]
+        action: ClientAction,
+    },
+    /// Manage projects
+    Project {
+        #[command(subcommand)]
+        action: ProjectAction,
+    },
+    /// Manage hourly rates
+    Rate {
+        #[command(subcommand)]
+        action: RateAction,
+    },
+    /// Manage hour targets shown in period reports
+    Target {
+        #[command(subcommand)]
+        action: TargetAction,
+    },
+    /// Manage the weekly work schedule used for the overtime balance
+    Schedule {
+        #[command(subcommand)]
+        action: ScheduleAction,
+    },
+    /// Show overtime or undertime against the work schedule
+    Balance {
+        #[arg(short = 'p', long, default_value = "week")]
+        period: Period,
+        /// Number of most recent periods to list
+        #[arg(short = 'n', long, default_value = "10")]
+        count: usize,
+    },
+    /// Manage vacation, sick days and public holidays
+    Off {
+        #[command(subcommand)]
+        action: OffAction,
+    },
+    /// Generate reports
+    Report {
+        #[command(subcommand)]
+        kind: ReportKind,
+    },
+    /// Create an invoice from a client's billable sessions
+    Invoice {
+        client: String,
+        /// First day of the invoice period (default: first day of last month)
+        #[arg(long, value_name = "DD.MM.YYYY")]
+        from: Option<String>,
+        /// Last day of the invoice period (default: last day of last month)
+        #[arg(long, value_name = "DD.MM.YYYY")]
+        to: Option<String>,
+        /// One line item per topic or per day
+        #[arg(long, default_value = "topic")]
+        per: InvoiceGrouping,
+        /// Tax rate in percent
+        #[arg(long, default_value = "0", value_parser = parse_amount)]
+        tax: f64,
+        /// Directory for the Markdown and HTML files
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+        /// Only show the invoice, don't save it or mark sessions
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List created invoices
+    Invoices,
+    /// Hours per topic and day of a week or month as a grid
+    Timesheet {
+        /// Week number, e.g. 42 (default: current week)
+        #[arg(long, value_name = "N", conflicts_with = "month")]
+        week: Option<u32>,
+        /// A month instead of a week (default: current month)
+        #[arg(long, value_name = "YYYY-MM", num_args = 0..=1)]
+        month: Option<Option<String>>,
+        #[arg(short = 'f', long, default_value = "terminal")]
+        format: TimesheetFormat,
+        #[command(flatten)]
+        topics: TopicArgs,
+    },
+    /// Protect sessions from changes, or show what is locked
+    Lock {
+        /// Lock every session started up to and including this day
+        #[arg(long, value_name = "DATE")]
+        until: Option<String>,
+        /// Lock a single session (repeatable)
+        #[arg(long = "session", value_name = "ID", add = ArgValueCompleter::new(complete::session_ids))]
+        sessions: Vec<i64>,
+    },
+    /// Remove the period lock, or unlock single sessions
+    Unlock {
+        /// Unlock a single session (repeatable)
+        #[arg(long = "session", value_name = "ID", add = ArgValueCompleter::new(complete::session_ids))]
+        sessions: Vec<i64>,
+    },
+    /// Copy the database to a file (defaults to the backup directory)
+    Backup { path: Option<String> },
+    /// Replace the database with a backup
+    Restore { file: String },
+    /// List backups and automatic snapshots
+    Backups,
+    /// Manage deleted sessions
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+    /// Revert the last change
+    Undo,
+    /// Re-apply the last undone change
+    Redo,
+    /// Show recent changes that can be undone
+    History {
+        #[arg(short = 'n', long, default_value = "10")]
+        count: usize,
+    },
 }
 
 fn main() -> Result<()> {
+    // Answers completion requests from the script `walrus completions` prints
+    CompleteEnv::with_factory(Cli::command).complete();
+
     let cli = Cli::parse();
-    let conn = db::init_db()?;
+
+    // Runs on every shell prompt, so it skips config, setup and migrations
+    if let Commands::Prompt { format, separator, waybar } = &cli.command {
+        return commands::prompt(format, separator, *waybar);
+    }
+    if let Commands::Completions { shell } = cli.command {
+        return commands::completions(shell);
+    }
+
+    // Works without loading the config, so a broken file can be fixed
+    if let Commands::Config { action } = &cli.command {
+        return match action {
+            ConfigAction::Get { key } => commands::config_get(key),
+            ConfigAction::Set { key, value } => commands::config_set(key, value),
+            ConfigAction::Unset { key } => commands::config_unset(key),
+            ConfigAction::List => commands::config_list(),
+        };
+    }
+
+    let config = config::load()?;
+    settings::init(settings::from_config(&config, cli.color)?);
+    // Confirmations can be turned off for good in the config
+    let confirmed = |yes: bool| yes || !config.defaults.confirm;
+    let mut conn = db::init_db(&config)?;
+
+    if !matches!(cli.command, Commands::FixStale { .. }) {
+        commands::check_stale(&conn, &config)?;
+    }
 
     match cli.command {
-        Commands::Start { topic } => commands::start(&conn, topic)?,
+        Commands::Start { topic } => commands::start(&conn, topic, &config.defaults.topic)?,
         Commands::Stop { topic } => match topic {
             Some(t) => commands::stop_topic(&conn, &t)?,
             None => commands::stop(&conn)?,
         },
-        Commands::Show { count, period } => commands::show(&conn, count, period)?,
-        Commands::List { count } => commands::list(&conn, count)?,
+        Commands::Show { args } => commands::show(&conn, &config, &args)?,
+        Commands::List { count, topics } => commands::list(&conn, count.unwrap_or(config.defaults.list_size), &topics)?,
         Commands::Add { topic, start, end } => commands::add(&conn, topic, start, end)?,
         Commands::Edit { id, topic, start, end } => commands::edit(&conn, id, topic, start, end)?,
-        Commands::Delete { id } => commands::delete(&conn, id)?,
-        Commands::Export => commands::export(&conn)?,
-        Commands::Reset => commands::reset(&conn)?,
+        Commands::Delete { ids, filter, yes } => commands::delete(&conn, &ids, &filter, confirmed(yes))?,
+        Commands::Export => commands::export(&conn, &config)?,
+        Commands::Prompt { .. } | Commands::Completions { .. } | Commands::Config { .. } => unreachable!("handled before opening the database"),
+        Commands::Status { watch } => commands::status(&conn, &config, watch)?,
+        Commands::Tui => tui::run(&conn)?,
+        Commands::Pomodoro { topic, work, pause, cycles, record_breaks } => {
+            commands::pomodoro(&conn, topic, &work, &pause, cycles, record_breaks)?
+        }
+        Commands::FixStale { at, last_activity, dry_run } => {
+            commands::fix_stale(&conn, &config, at, last_activity, dry_run)?
+        }
+        Commands::Reset { ids, filter, yes } => commands::reset(&conn, &ids, &filter, confirmed(yes))?,
+        Commands::Topic { action } => match action {
+            TopicAction::Rename { old, new, yes } => commands::topic_rename(&conn, &old, &new, confirmed(yes))?,
+            TopicAction::Merge { topics, into, yes } => commands::topic_merge(&conn, &topics, &into, confirmed(yes))?,
+        },
+        Commands::Client { action } => match action {
+            ClientAction::Add { name, currency, rate } => commands::client_add(&conn, &name, &currency, rate)?,
+            ClientAction::List => commands::client_list(&conn)?,
+            ClientAction::Rounding { name, mode, increment, per, clear } => {
+                commands::client_rounding(&conn, &config, &name, mode, increment, per, clear)?
+            }
+            ClientAction::Remove { name, yes } => commands::client_remove(&conn, &name, confirmed(yes))?,
+        },
+        Commands::Project { action } => match action {
+            ProjectAction::Add { name, client, topic, non_billable, rate } => {
+                commands::project_add(&conn, &name, &client, &topic, non_billable, rate)?
+            }
+            ProjectAction::Edit { name, topic, billable } => commands::project_edit(&conn, &name, topic, billable)?,
+            ProjectAction::List => commands::project_list(&conn)?,
+            ProjectAction::Remove { name } => commands::project_remove(&conn, &name)?,
+        },
+        Commands::Rate { action } => match action {
+            RateAction::Set { rate, client, project, topic, from } => {
+                commands::rate_set(&conn, rate, client, project, topic, from)?
+            }
+            RateAction::List => commands::rate_list(&conn)?,
+            RateAction::Remove { id } => commands::rate_remove(&conn, id)?,
+        },
+        Commands::Target { action } => match action {
+            TargetAction::Set { hours, per, topic } => commands::target_set(&conn, hours, per, topic)?,
+            TargetAction::List => commands::target_list(&conn)?,
+            TargetAction::Remove { id } => commands::target_remove(&conn, id)?,
+        },
+        Commands::Schedule { action } => match action {
+            ScheduleAction::Set { hours, from } => commands::schedule_set(&conn, &hours, from)?,
+            ScheduleAction::List => commands::schedule_list(&conn)?,
+            ScheduleAction::Remove { id } => commands::schedule_remove(&conn, id)?,
+        },
+        Commands::Balance { period, count } => commands::balance(&conn, period, count)?,
+        Commands::Off { action } => match action {
+            OffAction::Add { date, to, kind, half, name } => commands::off_add(&conn, &date, to, kind, half, name)?,
+            OffAction::List { year } => commands::off_list(&conn, year)?,
+            OffAction::Remove { date, to } => commands::off_remove(&conn, &date, to)?,
+            OffAction::Import { file, country, region, year, kind } => {
+                commands::off_import(&conn, file, country, region, year, kind)?
+            }
+        },
+        Commands::Report { kind } => match kind {
+            ReportKind::Billing { month } => commands::report_billing(&conn, &config, month)?,
+        },
+        Commands::Invoice { client, from, to, per, tax, output, dry_run } => {
+            commands::invoice(&conn, &config, &client, from, to, per, tax, output, dry_run)?
+        }
+        Commands::Invoices => commands::invoices(&conn)?,
+        Commands::Timesheet { week, month, format, topics } => commands::timesheet(&conn, week, month, format, &topics)?,
+        Commands::Lock { until, sessions } => commands::lock(&conn, until, &sessions)?,
+        Commands::Unlock { sessions } => commands::unlock(&conn, &sessions)?,
+        Commands::Backup { path } => commands::backup(&conn, path)?,
+        Commands::Restore { file } => commands::restore(&mut conn, &file)?,
+        Commands::Backups => commands::backups()?,
+        Commands::Trash { action } => match action {
+            TrashAction::List => commands::trash_list(&conn)?,
+            TrashAction::Restore { id } => commands::trash_restore(&conn, id)?,
+            TrashAction::Empty { older_than, yes } => commands::trash_empty(&conn, older_than, confirmed(yes))?,
+        },
+        Commands::Undo => commands::undo(&conn)?,
+        Commands::Redo => commands::redo(&conn)?,
+        Commands::History { count } => commands::history(&conn, count)?,
     }
 
     Ok(())
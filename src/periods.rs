@@ -0,0 +1,181 @@
+// Calendar periods counted back from today. Everything that walks days, weeks,
+// months, quarters or years goes through here so reports agree on where
+// periods start.
+
+use chrono::{Datelike, Days, Months, NaiveDate, NaiveDateTime, Weekday};
+use crate::{settings, Period};
+
+pub struct Range {
+    pub start: NaiveDateTime,
+    /// Exclusive; for the current period this is now rather than its natural end
+    pub end: NaiveDateTime,
+    /// Last day of the period, even if it is still ahead
+    pub last_day: NaiveDate,
+    pub label: String,
+}
+
+/// The period `back` periods before the current one (0 is the current one).
+pub fn range(period: Period, back: usize, now: NaiveDateTime) -> Range {
+    let (first, next) = bounds(period, back, now.date());
+    let end = if back == 0 { now } else { next.and_hms_opt(0, 0, 0).unwrap() };
+
+    Range {
+        start: first.and_hms_opt(0, 0, 0).unwrap(),
+        end,
+        last_day: next.pred_opt().unwrap(),
+        label: label(period, back, first, next),
+    }
+}
+
+/// The period that contains `date`, which must not be after `now`.
+pub fn containing(period: Period, date: NaiveDate, now: NaiveDateTime) -> Range {
+    let (first, next) = bounds(period, 0, date);
+    let back = (now.date() - first).num_days().max(0) as usize;
+
+    Range {
+        start: first.and_hms_opt(0, 0, 0).unwrap(),
+        end: next.and_hms_opt(0, 0, 0).unwrap().min(now),
+        last_day: next.pred_opt().unwrap(),
+        label: label(period, back, first, next),
+    }
+}
+
+/// The most recent week with the given number, up to a year back.
+pub fn numbered_week(number: u32, now: NaiveDateTime) -> Option<Range> {
+    (0..=53)
+        .map(|back| range(Period::Week, back, now))
+        .find(|r| week_number(r.start.date()) == number)
+}
+
+/// First day of the period and first day of the following one.
+fn bounds(period: Period, back: usize, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    match period {
+        Period::Day => {
+            let day = today - Days::new(back as u64);
+            (day, day + Days::new(1))
+        }
+        Period::Week => {
+            let first = today
+                - Days::new(today.weekday().days_since(settings::get().week_start) as u64)
+                - Days::new(7 * back as u64);
+            (first, first + Days::new(7))
+        }
+        Period::Month => {
+            let first = today.with_day(1).unwrap() - Months::new(back as u32);
+            (first, first + Months::new(1))
+        }
+        Period::Quarter => {
+            let first = today.with_day(1).unwrap()
+                - Months::new(months_into_year(today) % 3)
+                - Months::new(3 * back as u32);
+            (first, first + Months::new(3))
+        }
+        Period::Year => {
+            let first = today.with_day(1).unwrap()
+                - Months::new(months_into_year(today))
+                - Months::new(12 * back as u32);
+            (first, first + Months::new(12))
+        }
+    }
+}
+
+/// Months since the start of the (fiscal) year `date` is in.
+fn months_into_year(date: NaiveDate) -> u32 {
+    (date.month0() + 13 - settings::get().fiscal_year_start) % 12
+}
+
+/// `2026` for calendar years, `FY 2026/27` for fiscal years starting in 2026.
+fn year_name(date: NaiveDate) -> String {
+    let first = date.with_day(1).unwrap() - Months::new(months_into_year(date));
+    if settings::get().fiscal_year_start == 1 {
+        first.year().to_string()
+    } else {
+        format!("FY {}/{:02}", first.year(), (first.year() + 1) % 100)
+    }
+}
+
+/// The ISO week number for weeks starting on Monday. For other week starts,
+/// week 1 is the week containing January 1st.
+fn week_number(first: NaiveDate) -> u32 {
+    let week_start = settings::get().week_start;
+    if week_start == Weekday::Mon {
+        return first.iso_week().week();
+    }
+
+    let year = (first + Days::new(6)).year();
+    let jan1 = NaiveDate::from_ymd_opt(year, 1, 1).unwrap();
+    let week1 = jan1 - Days::new(jan1.weekday().days_since(week_start) as u64);
+    ((first - week1).num_days() / 7 + 1) as u32
+}
+
+fn label(period: Period, back: usize, first: NaiveDate, next: NaiveDate) -> String {
+    let last = next.pred_opt().unwrap();
+    match period {
+        Period::Day => match back {
+            0 => "Today".to_string(),
+            1 => "Yesterday".to_string(),
+            _ => format!("{}, {}", first.format("%A"), settings::date(first)),
+        },
+        Period::Week => format!(
+            "Week {} ({} - {})",
+            week_number(first),
+            settings::date(first),
+            settings::date(last)
+        ),
+        Period::Month => first.format("%B %Y").to_string(),
+        Period::Quarter => format!(
+            "Q{} {} ({} - {})",
+            months_into_year(first) / 3 + 1,
+            year_name(first),
+            settings::date(first),
+            settings::date(last)
+        ),
+        Period::Year if settings::get().fiscal_year_start == 1 => year_name(first),
+        Period::Year => format!("{} ({} - {})", year_name(first), settings::date(first), settings::date(last)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn now() -> NaiveDateTime {
+        ymd(2026, 10, 18).and_hms_opt(12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn containing_finds_the_period_around_a_date() {
+        let month = containing(Period::Month, ymd(2026, 9, 15), now());
+        assert_eq!(month.start, ymd(2026, 9, 1).and_hms_opt(0, 0, 0).unwrap());
+        assert_eq!(month.end, ymd(2026, 10, 1).and_hms_opt(0, 0, 0).unwrap());
+        assert_eq!(month.last_day, ymd(2026, 9, 30));
+
+        let quarter = containing(Period::Quarter, ymd(2026, 5, 10), now());
+        assert_eq!(quarter.start.date(), ymd(2026, 4, 1));
+        assert_eq!(quarter.last_day, ymd(2026, 6, 30));
+
+        let year = containing(Period::Year, ymd(2025, 2, 28), now());
+        assert_eq!(year.start.date(), ymd(2025, 1, 1));
+        assert_eq!(year.last_day, ymd(2025, 12, 31));
+    }
+
+    #[test]
+    fn containing_weeks_start_on_the_configured_day() {
+        // Wednesday, with the default week start on Monday
+        let week = containing(Period::Week, ymd(2026, 10, 7), now());
+        assert_eq!(week.start.date(), ymd(2026, 10, 5));
+        assert_eq!(week.last_day, ymd(2026, 10, 11));
+    }
+
+    #[test]
+    fn containing_the_current_period_ends_now() {
+        let week = containing(Period::Week, ymd(2026, 10, 14), now());
+        assert_eq!(week.start.date(), ymd(2026, 10, 12));
+        assert_eq!(week.end, now());
+        assert_eq!(week.last_day, ymd(2026, 10, 18));
+    }
+}
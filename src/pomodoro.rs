@@ -0,0 +1,141 @@
+// Foreground pomodoro timer. Every work interval is recorded as a session
+// while it runs, so an interrupted interval still ends up in the stats.
+
+use rusqlite::Connection;
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::{display, journal, queries};
+
+pub const BREAK_TOPIC: &str = "break";
+
+pub struct Settings {
+    pub topic: String,
+    pub work: Duration,
+    pub pause: Duration,
+    pub cycles: u32,
+    /// Record breaks as sessions of the `break` topic
+    pub record_breaks: bool,
+}
+
+/// Parses an interval length like `25m` or `1h`.
+pub fn parse_interval(s: &str) -> Result<Duration> {
+    let invalid = || anyhow::anyhow!("Invalid interval '{}'. Use e.g. 25m or 1h", s);
+
+    let trimmed = s.trim();
+    let unit = trimmed.chars().last().ok_or_else(invalid)?;
+    let number: u64 = trimmed[..trimmed.len() - unit.len_utf8()].parse().map_err(|_| invalid())?;
+    if number == 0 {
+        anyhow::bail!("Interval '{}' must be longer than zero", s);
+    }
+
+    let seconds = match unit {
+        'm' => number.checked_mul(60),
+        'h' => number.checked_mul(3600),
+        _ => None,
+    };
+    seconds.map(Duration::from_secs).ok_or_else(invalid)
+}
+
+enum Phase {
+    Work,
+    Break,
+}
+
+enum Outcome {
+    Finished,
+    Interrupted,
+}
+
+pub fn run(conn: &Connection, settings: &Settings) -> Result<()> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    ctrlc::set_handler(move || flag.store(true, Ordering::SeqCst))?;
+
+    let mut completed = 0;
+    for cycle in 1..=settings.cycles {
+        if let Outcome::Interrupted = interval(conn, settings, Phase::Work, cycle, &interrupted)? {
+            break;
+        }
+        completed += 1;
+
+        if cycle == settings.cycles {
+            break;
+        }
+        if let Outcome::Interrupted = interval(conn, settings, Phase::Break, cycle, &interrupted)? {
+            break;
+        }
+    }
+
+    println!("{} of {} pomodoros completed", completed, settings.cycles);
+    Ok(())
+}
+
+/// Counts down one work interval or break. Work, and breaks if they are
+/// recorded, are tracked as a session that is stopped when the interval
+/// ends or is interrupted.
+fn interval(conn: &Connection, settings: &Settings, phase: Phase, cycle: u32, interrupted: &AtomicBool) -> Result<Outcome> {
+    let (name, topic, length, record) = match phase {
+        Phase::Work => ("Work", settings.topic.as_str(), settings.work, true),
+        Phase::Break => ("Break", BREAK_TOPIC, settings.pause, settings.record_breaks),
+    };
+
+    let session_id = if record {
+        if queries::get_active_session_for_topic(conn, topic)?.is_some() {
+            anyhow::bail!("Session for '{}' is already active! Stop it first with 'walrus stop {}'", topic, topic);
+        }
+        Some(journal::transaction(conn, &format!("start {}", topic), |tx| queries::start_session(tx, topic))?)
+    } else {
+        None
+    };
+
+    let started = Instant::now();
+    let outcome = loop {
+        if interrupted.load(Ordering::SeqCst) {
+            break Outcome::Interrupted;
+        }
+        let elapsed = started.elapsed();
+        if elapsed >= length {
+            break Outcome::Finished;
+        }
+        display::print_countdown(name, cycle, settings.cycles, topic, length - elapsed);
+        std::thread::sleep(Duration::from_millis(200));
+    };
+
+    if let Some(id) = session_id {
+        let is_pomodoro = matches!((&phase, &outcome), (Phase::Work, Outcome::Finished));
+        journal::transaction(conn, &format!("stop {}", topic), |tx| {
+            queries::stop_session(tx, id)?;
+            if is_pomodoro {
+                queries::mark_pomodoro(tx, id)?;
+            }
+            Ok(())
+        })?;
+    }
+
+    let elapsed = started.elapsed().as_secs();
+    match outcome {
+        Outcome::Finished => println!("\r{} {}/{}  {}  done   \x07", name, cycle, settings.cycles, topic),
+        Outcome::Interrupted => println!(
+            "\r{} {}/{}  {}  stopped after {:02}:{:02}",
+            name, cycle, settings.cycles, topic, elapsed / 60, elapsed % 60
+        ),
+    }
+
+    Ok(outcome)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_interval_lengths() {
+        assert_eq!(parse_interval("25m").unwrap(), Duration::from_secs(25 * 60));
+        assert_eq!(parse_interval("1h").unwrap(), Duration::from_secs(3600));
+        assert!(parse_interval("0m").is_err());
+        assert!(parse_interval("5d").is_err());
+        assert!(parse_interval("99999999999999999h").is_err());
+    }
+}
@@ -1,54 +1,160 @@
 use rusqlite::{Connection, OptionalExtension};
 use anyhow::Result;
 use chrono::{DateTime, NaiveDateTime, Local, TimeZone};
+use crate::{journal, settings};
+use crate::targets::Progress;
+use crate::absences::Absence;
 
 pub struct Session {
     pub id: i64,
     pub topic: String,
     pub start: DateTime<chrono::FixedOffset>,
     pub end: Option<DateTime<chrono::FixedOffset>>,
+    /// Locked on its own (e.g. invoiced) or by the period lock
+    pub locked: bool,
 }
 
 pub struct PeriodStats {
     pub label: String,
     pub topics: Vec<(String, f64)>,
+    pub progress: Vec<Progress>,
+    pub absences: Vec<Absence>,
+    /// Total after rounding, if any rounding is configured
+    pub rounded_total: Option<f64>,
+    /// Completed pomodoros per day
+    pub pomodoros: Vec<(chrono::NaiveDate, usize)>,
 }
 
-pub fn get_active_session(conn: &Connection) -> Result<Option<Session>> {
-    let result = conn.query_row(
-        "SELECT id, topic, start_time FROM sessions WHERE end_time IS NULL",
-        [],
-        |row| {
-            let id: i64 = row.get(0)?;
-            let topic: String = row.get(1)?;
-            let start_str: String = row.get(2)?;
-            Ok((id, topic, start_str))
-        },
-    ).optional()?;
+/// Topic patterns to report on. A session is included if it matches any of
+/// `include` (or `include` is empty) and none of `exclude`.
+#[derive(Default, Clone)]
+pub struct TopicFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
 
-    if let Some((id, topic, start_str)) = result {
-        let start = DateTime::parse_from_rfc3339(&start_str)?;
-        Ok(Some(Session { id, topic, start, end: None }))
+impl TopicFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+}
+
+/// Selects sessions for bulk operations. All set criteria must match; an
+/// empty filter matches nothing on its own, callers decide what that means.
+#[derive(Default)]
+pub struct SessionFilter {
+    /// Inclusive id ranges, a single id is a range of one
+    pub ids: Vec<(i64, i64)>,
+    pub topics: TopicFilter,
+    /// RFC3339 bounds on the session start
+    pub before: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Only sessions that are not on an invoice yet
+    pub uninvoiced: bool,
+    /// Sessions in the trash match as well
+    pub include_trashed: bool,
+}
+
+impl SessionFilter {
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+            && self.topics.is_empty()
+            && self.before.is_none()
+            && self.from.is_none()
+            && self.to.is_none()
+            && !self.uninvoiced
+    }
+
+    fn where_clause(&self) -> (String, Vec<rusqlite::types::Value>) {
+        let mut clauses = vec![if self.include_trashed { "1" } else { "deleted_at IS NULL" }.to_string()];
+        let mut params: Vec<rusqlite::types::Value> = Vec::new();
+
+        if !self.ids.is_empty() {
+            let ranges: Vec<_> = self.ids.iter().map(|&(lo, hi)| {
+                params.push(lo.into());
+                params.push(hi.into());
+                "id BETWEEN ? AND ?"
+            }).collect();
+            clauses.push(format!("({})", ranges.join(" OR ")));
+        }
+
+        if let Some(clause) = topic_clause(&self.topics, &mut params) {
+            clauses.push(clause);
+        }
+
+        if let Some(before) = &self.before {
+            params.push(before.clone().into());
+            clauses.push("julianday(start_time) < julianday(?)".to_string());
+        }
+
+        if let Some(from) = &self.from {
+            params.push(from.clone().into());
+            clauses.push("julianday(start_time) >= julianday(?)".to_string());
+        }
+
+        if let Some(to) = &self.to {
+            params.push(to.clone().into());
+            clauses.push("julianday(start_time) < julianday(?)".to_string());
+        }
+
+        if self.uninvoiced {
+            clauses.push("invoice_id IS NULL".to_string());
+        }
+
+        (clauses.join(" AND "), params)
+    }
+}
+
+// Selects whether a session row is locked, either on its own or because it
+// started before the period lock boundary.
+const LOCKED: &str =
+    "(locked = 1 OR julianday(start_time) < COALESCE((SELECT julianday(until) FROM period_lock), 0))";
+
+/// Builds a condition for the topic filter, or None if it has no patterns.
+fn topic_clause(filter: &TopicFilter, params: &mut Vec<rusqlite::types::Value>) -> Option<String> {
+    let mut clauses = Vec::new();
+
+    if !filter.include.is_empty() {
+        let include: Vec<_> = filter.include.iter().map(|p| {
+            params.push(p.clone().into());
+            "topic_match(?, topic)"
+        }).collect();
+        clauses.push(format!("({})", include.join(" OR ")));
+    }
+
+    for pattern in &filter.exclude {
+        params.push(pattern.clone().into());
+        clauses.push("NOT topic_match(?, topic)".to_string());
+    }
+
+    if clauses.is_empty() {
+        None
     } else {
-        Ok(None)
+        Some(format!("({})", clauses.join(" AND ")))
     }
 }
 
 pub fn get_active_session_for_topic(conn: &Connection, topic: &str) -> Result<Option<Session>> {
     let result = conn.query_row(
-        "SELECT id, topic, start_time FROM sessions WHERE end_time IS NULL AND topic = ?1",
+        &format!(
+            "SELECT id, topic, start_time, {} FROM sessions
+             WHERE end_time IS NULL AND deleted_at IS NULL AND topic = ?1",
+            LOCKED
+        ),
         [topic],
         |row| {
             let id: i64 = row.get(0)?;
             let topic: String = row.get(1)?;
             let start_str: String = row.get(2)?;
-            Ok((id, topic, start_str))
+            let locked: bool = row.get(3)?;
+            Ok((id, topic, start_str, locked))
         },
     ).optional()?;
 
-    if let Some((id, topic, start_str)) = result {
+    if let Some((id, topic, start_str, locked)) = result {
         let start = DateTime::parse_from_rfc3339(&start_str)?;
-        Ok(Some(Session { id, topic, start, end: None }))
+        Ok(Some(Session { id, topic, start, end: None, locked }))
     } else {
         Ok(None)
     }
@@ -56,7 +162,9 @@ pub fn get_active_session_for_topic(conn: &Connection, topic: &str) -> Result<Op
 
 pub fn get_all_active_sessions(conn: &Connection) -> Result<Vec<(i64, String)>> {
     let mut stmt = conn.prepare(
-        "SELECT id, topic FROM sessions WHERE end_time IS NULL ORDER BY start_time DESC"
+        "SELECT id, topic FROM sessions
+         WHERE end_time IS NULL AND deleted_at IS NULL
+         ORDER BY start_time DESC"
     )?;
 
     let sessions = stmt.query_map([], |row| {
@@ -67,55 +175,117 @@ pub fn get_all_active_sessions(conn: &Connection) -> Result<Vec<(i64, String)>>
     result.map_err(Into::into)
 }
 
-pub fn get_sessions(conn: &Connection, limit: usize) -> Result<Vec<Session>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, topic, start_time, end_time
+/// Tracked hours of sessions started in `[start, end)`, counting running
+/// sessions up to `now`.
+pub fn get_period_total(conn: &Connection, start: NaiveDateTime, end: NaiveDateTime, now: DateTime<Local>) -> Result<f64> {
+    let finished = get_period_stats(conn, start, end, &TopicFilter::default())?
+        .iter()
+        .fold(0.0, |sum, (_, hours)| sum + hours);
+    let running = get_active_sessions(conn)?
+        .iter()
+        .filter(|s| s.start.with_timezone(&Local).naive_local() >= start)
+        .fold(0.0, |sum, s| sum + now.signed_duration_since(s.start).num_seconds() as f64 / 3600.0);
+    Ok(finished + running)
+}
+
+/// All running sessions, oldest first.
+pub fn get_active_sessions(conn: &Connection) -> Result<Vec<Session>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, topic, start_time, {} FROM sessions
+         WHERE end_time IS NULL AND deleted_at IS NULL
+         ORDER BY julianday(start_time)",
+        LOCKED
+    ))?;
+
+    let sessions = stmt.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, bool>(3)?))
+    })?;
+
+    let mut result = Vec::new();
+    for session in sessions {
+        let (id, topic, start_str, locked) = session?;
+        let start = DateTime::parse_from_rfc3339(&start_str)?;
+        result.push(Session { id, topic, start, end: None, locked });
+    }
+
+    Ok(result)
+}
+
+/// The latest stop of any other session after `since`.
+pub fn get_last_activity(conn: &Connection, id: i64, since: &DateTime<chrono::FixedOffset>) -> Result<Option<DateTime<chrono::FixedOffset>>> {
+    let last: Option<String> = conn.query_row(
+        "SELECT end_time FROM sessions
+         WHERE id != ?1 AND deleted_at IS NULL AND end_time IS NOT NULL
+           AND julianday(end_time) > julianday(?2)
+         ORDER BY julianday(end_time) DESC
+         LIMIT 1",
+        rusqlite::params![id, since.to_rfc3339()],
+        |row| row.get(0),
+    ).optional()?;
+
+    last.map(|t| DateTime::parse_from_rfc3339(&t).map_err(Into::into)).transpose()
+}
+
+pub fn get_sessions(conn: &Connection, limit: usize, topics: &TopicFilter) -> Result<Vec<Session>> {
+    let mut params = Vec::new();
+    let topic_filter = topic_clause(topics, &mut params)
+        .map(|c| format!("AND {}", c))
+        .unwrap_or_default();
+    params.push((limit as i64).into());
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, topic, start_time, end_time, {}
          FROM sessions
+         WHERE deleted_at IS NULL {}
          ORDER BY start_time DESC
-         LIMIT ?1"
-    )?;
+         LIMIT ?",
+        LOCKED, topic_filter
+    ))?;
 
-    let sessions = stmt.query_map([limit], |row| {
+    let sessions = stmt.query_map(rusqlite::params_from_iter(params), |row| {
         let id: i64 = row.get(0)?;
         let topic: String = row.get(1)?;
         let start_str: String = row.get(2)?;
         let end_str: Option<String> = row.get(3)?;
-        Ok((id, topic, start_str, end_str))
+        let locked: bool = row.get(4)?;
+        Ok((id, topic, start_str, end_str, locked))
     })?;
 
     let mut result = Vec::new();
     for session in sessions {
-        let (id, topic, start_str, end_str) = session?;
+        let (id, topic, start_str, end_str, locked) = session?;
         let start = DateTime::parse_from_rfc3339(&start_str)?;
         let end = end_str.map(|s| DateTime::parse_from_rfc3339(&s)).transpose()?;
-        result.push(Session { id, topic, start, end });
+        result.push(Session { id, topic, start, end, locked });
     }
 
     Ok(result)
 }
 
 pub fn get_all_sessions_for_export(conn: &Connection) -> Result<Vec<Session>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, topic, start_time, end_time
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, topic, start_time, end_time, {}
          FROM sessions
-         WHERE end_time IS NOT NULL
-         ORDER BY start_time ASC"
-    )?;
+         WHERE end_time IS NOT NULL AND deleted_at IS NULL
+         ORDER BY start_time ASC",
+        LOCKED
+    ))?;
 
     let sessions = stmt.query_map([], |row| {
         let id: i64 = row.get(0)?;
         let topic: String = row.get(1)?;
         let start_str: String = row.get(2)?;
         let end_str: String = row.get(3)?;
-        Ok((id, topic, start_str, end_str))
+        let locked: bool = row.get(4)?;
+        Ok((id, topic, start_str, end_str, locked))
     })?;
 
     let mut result = Vec::new();
     for session in sessions {
-        let (id, topic, start_str, end_str) = session?;
+        let (id, topic, start_str, end_str, locked) = session?;
         let start = DateTime::parse_from_rfc3339(&start_str)?;
         let end = DateTime::parse_from_rfc3339(&end_str)?;
-        result.push(Session { id, topic, start, end: Some(end) });
+        result.push(Session { id, topic, start, end: Some(end), locked });
     }
 
     Ok(result)
@@ -125,6 +295,7 @@ pub fn get_period_stats(
     conn: &Connection,
     start: NaiveDateTime,
     end: NaiveDateTime,
+    topics: &TopicFilter,
 ) -> Result<Vec<(String, f64)>> {
     // Convert NaiveDateTime to timezone-aware DateTime in RFC3339 format
     // to match the format stored in the database
@@ -136,126 +307,633 @@ pub fn get_period_stats(
     let start_rfc3339 = start_dt.to_rfc3339();
     let end_rfc3339 = end_dt.to_rfc3339();
 
-    let mut stmt = conn.prepare(
+    let mut params: Vec<rusqlite::types::Value> = vec![start_rfc3339.into(), end_rfc3339.into()];
+    let topic_filter = topic_clause(topics, &mut params)
+        .map(|c| format!("AND {}", c))
+        .unwrap_or_default();
+
+    let mut stmt = conn.prepare(&format!(
         "SELECT topic, SUM((julianday(end_time) - julianday(start_time)) * 24) as hours
          FROM sessions
          WHERE end_time IS NOT NULL
-           AND start_time >= ?1
-           AND start_time < ?2
+           AND deleted_at IS NULL
+           AND start_time >= ?
+           AND start_time < ?
+           {}
          GROUP BY topic
-         ORDER BY hours DESC"
-    )?;
+         ORDER BY hours DESC",
+        topic_filter
+    ))?;
 
-    let topics = stmt.query_map(
-        rusqlite::params![start_rfc3339, end_rfc3339],
+    let rows = stmt.query_map(
+        rusqlite::params_from_iter(params),
         |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
     )?;
 
     let mut result = Vec::new();
-    for topic in topics {
-        result.push(topic?);
+    for row in rows {
+        result.push(row?);
+    }
+
+    Ok(result)
+}
+
+/// Finished sessions started in `[start, end)` as (topic, day, hours).
+pub fn get_period_sessions(
+    conn: &Connection,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    topics: &TopicFilter,
+) -> Result<Vec<(String, chrono::NaiveDate, f64)>> {
+    let filter = SessionFilter {
+        topics: topics.clone(),
+        from: Some(to_rfc3339(start)?),
+        to: Some(to_rfc3339(end)?),
+        ..Default::default()
+    };
+
+    Ok(get_matching_sessions(conn, &filter)?
+        .into_iter()
+        .filter(|(s, _)| s.end.is_some())
+        .map(|(s, hours)| (s.topic, s.start.date_naive(), hours))
+        .collect())
+}
+
+/// Marks a session as a completed pomodoro.
+pub fn mark_pomodoro(conn: &Connection, id: i64) -> Result<()> {
+    let before = journal::session_image(conn, id)?;
+    conn.execute("UPDATE sessions SET pomodoro = 1 WHERE id = ?1", [id])?;
+    journal::record_change(conn, id, before, journal::session_image(conn, id)?)?;
+    Ok(())
+}
+
+/// Completed pomodoros per day, for sessions started in `[start, end)`.
+pub fn get_pomodoro_counts(
+    conn: &Connection,
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    topics: &TopicFilter,
+) -> Result<Vec<(chrono::NaiveDate, usize)>> {
+    let mut params: Vec<rusqlite::types::Value> = vec![to_rfc3339(start)?.into(), to_rfc3339(end)?.into()];
+    let topic_filter = topic_clause(topics, &mut params)
+        .map(|c| format!("AND {}", c))
+        .unwrap_or_default();
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT substr(s.start_time, 1, 10) AS day, COUNT(*)
+         FROM sessions s
+         WHERE s.pomodoro = 1
+           AND s.end_time IS NOT NULL
+           AND s.deleted_at IS NULL
+           AND julianday(s.start_time) >= julianday(?)
+           AND julianday(s.start_time) < julianday(?)
+           {}
+         GROUP BY day
+         ORDER BY day",
+        topic_filter
+    ))?;
+
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+    })?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        let (day, count) = row?;
+        result.push((chrono::NaiveDate::parse_from_str(&day, "%Y-%m-%d")?, count as usize));
     }
 
     Ok(result)
 }
 
-pub fn start_session(conn: &Connection, topic: &str) -> Result<()> {
+pub fn start_session(conn: &Connection, topic: &str) -> Result<i64> {
     let now = Local::now().to_rfc3339();
+    ensure_period_unlocked(conn, &now)?;
     conn.execute(
         "INSERT INTO sessions (topic, start_time) VALUES (?1, ?2)",
         [Some(topic), Some(&now)],
     )?;
-    Ok(())
+    let id = conn.last_insert_rowid();
+    journal::record_change(conn, id, None, journal::session_image(conn, id)?)?;
+    Ok(id)
 }
 
 pub fn stop_session(conn: &Connection, id: i64) -> Result<()> {
+    ensure_unlocked(conn, id)?;
     let now = Local::now().to_rfc3339();
+    let before = journal::session_image(conn, id)?;
     conn.execute(
         "UPDATE sessions SET end_time = ?1 WHERE id = ?2",
         [&now, &id.to_string()],
     )?;
+    journal::record_change(conn, id, before, journal::session_image(conn, id)?)?;
     Ok(())
 }
 
 pub fn delete_all_sessions(conn: &Connection) -> Result<()> {
+    let ids = conn.prepare("SELECT id FROM sessions")?
+        .query_map([], |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for id in ids {
+        ensure_unlocked(conn, id)?;
+        ensure_not_invoiced(conn, id)?;
+        journal::record_change(conn, id, journal::session_image(conn, id)?, None)?;
+    }
+
     conn.execute("DELETE FROM sessions", [])?;
     Ok(())
 }
 
+/// Moves a session to the trash. Returns false if there is no such session
+/// outside the trash.
 pub fn delete_session(conn: &Connection, id: i64) -> Result<bool> {
+    ensure_unlocked(conn, id)?;
+    let now = Local::now().to_rfc3339();
+    let before = journal::session_image(conn, id)?;
+    let rows = conn.execute(
+        "UPDATE sessions SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+        rusqlite::params![now, id],
+    )?;
+    journal::record_change(conn, id, before, journal::session_image(conn, id)?)?;
+    Ok(rows > 0)
+}
+
+/// Permanently removes a session, bypassing the trash.
+pub fn purge_session(conn: &Connection, id: i64) -> Result<bool> {
+    ensure_unlocked(conn, id)?;
+    ensure_not_invoiced(conn, id)?;
+    let before = journal::session_image(conn, id)?;
     let rows = conn.execute("DELETE FROM sessions WHERE id = ?1", [id])?;
+    journal::record_change(conn, id, before, None)?;
     Ok(rows > 0)
 }
 
+pub fn get_trashed_sessions(conn: &Connection) -> Result<Vec<(Session, DateTime<chrono::FixedOffset>)>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, topic, start_time, end_time, deleted_at, {}
+         FROM sessions
+         WHERE deleted_at IS NOT NULL
+         ORDER BY deleted_at DESC",
+        LOCKED
+    ))?;
+
+    let sessions = stmt.query_map([], |row| {
+        let id: i64 = row.get(0)?;
+        let topic: String = row.get(1)?;
+        let start_str: String = row.get(2)?;
+        let end_str: Option<String> = row.get(3)?;
+        let deleted_str: String = row.get(4)?;
+        let locked: bool = row.get(5)?;
+        Ok((id, topic, start_str, end_str, deleted_str, locked))
+    })?;
+
+    let mut result = Vec::new();
+    for session in sessions {
+        let (id, topic, start_str, end_str, deleted_str, locked) = session?;
+        let start = DateTime::parse_from_rfc3339(&start_str)?;
+        let end = end_str.map(|s| DateTime::parse_from_rfc3339(&s)).transpose()?;
+        let deleted = DateTime::parse_from_rfc3339(&deleted_str)?;
+        result.push((Session { id, topic, start, end, locked }, deleted));
+    }
+
+    Ok(result)
+}
+
+pub fn restore_session(conn: &Connection, id: i64) -> Result<bool> {
+    ensure_unlocked(conn, id)?;
+    let before = journal::session_image(conn, id)?;
+    let rows = conn.execute(
+        "UPDATE sessions SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+        [id],
+    )?;
+    journal::record_change(conn, id, before, journal::session_image(conn, id)?)?;
+    Ok(rows > 0)
+}
+
+/// Permanently removes trashed sessions deleted before `cutoff` (or all of
+/// them). Locked sessions and sessions on an invoice stay in the trash.
+/// Returns the number of sessions removed and kept.
+pub fn empty_trash(conn: &Connection, cutoff: Option<DateTime<Local>>) -> Result<(usize, usize)> {
+    let cutoff = cutoff.map(|c| c.to_rfc3339());
+    let candidates = conn.prepare(&format!(
+        "SELECT id, NOT {} AND invoice_id IS NULL FROM sessions
+         WHERE deleted_at IS NOT NULL
+           AND (?1 IS NULL OR julianday(deleted_at) < julianday(?1))",
+        LOCKED
+    ))?
+        .query_map([&cutoff], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, bool>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (ids, kept): (Vec<_>, Vec<_>) = candidates.into_iter().partition(|&(_, removable)| removable);
+    for &(id, _) in &ids {
+        journal::record_change(conn, id, journal::session_image(conn, id)?, None)?;
+        conn.execute("DELETE FROM sessions WHERE id = ?1", [id])?;
+    }
+
+    Ok((ids.len(), kept.len()))
+}
+
 pub fn session_exists(conn: &Connection, id: i64) -> Result<bool> {
     let exists: bool = conn.query_row(
-        "SELECT 1 FROM sessions WHERE id = ?1",
+        "SELECT 1 FROM sessions WHERE id = ?1 AND deleted_at IS NULL",
         [id],
         |_| Ok(true),
     ).optional()?.unwrap_or(false);
     Ok(exists)
 }
 
+/// Fails if the session is locked, naming the invoice if it is on one.
+pub fn ensure_unlocked(conn: &Connection, id: i64) -> Result<()> {
+    let row = conn.query_row(
+        "SELECT s.start_time, s.locked, i.number
+         FROM sessions s
+         LEFT JOIN invoices i ON i.id = s.invoice_id
+         WHERE s.id = ?1",
+        [id],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?, row.get::<_, Option<String>>(2)?)),
+    ).optional()?;
+
+    let Some((start, locked, invoice)) = row else {
+        return Ok(());
+    };
+
+    if locked {
+        let reason = invoice.map(|n| format!(" (invoice {})", n)).unwrap_or_default();
+        anyhow::bail!("Session {} is locked{}. Unlock it with 'walrus unlock --session {}'", id, reason, id);
+    }
+
+    if let Some(boundary) = get_lock_boundary(conn)? {
+        if DateTime::parse_from_rfc3339(&start)? < boundary {
+            anyhow::bail!(
+                "Session {} is in the locked period (up to {}). Unlock it with 'walrus unlock'",
+                id, settings::date(locked_until(boundary))
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Fails if the session is on an invoice. The undo journal doesn't record
+/// invoices, so such a session could only come back unbilled.
+pub fn ensure_not_invoiced(conn: &Connection, id: i64) -> Result<()> {
+    let invoice = conn.query_row(
+        "SELECT i.number FROM sessions s JOIN invoices i ON i.id = s.invoice_id WHERE s.id = ?1",
+        [id],
+        |row| row.get::<_, String>(0),
+    ).optional()?;
+
+    if let Some(number) = invoice {
+        anyhow::bail!("Session {} is on invoice {} and can't be deleted for good", id, number);
+    }
+    Ok(())
+}
+
+/// Fails if a session starting at `start` (RFC3339) would fall into the
+/// locked period.
+pub fn ensure_period_unlocked(conn: &Connection, start: &str) -> Result<()> {
+    if let Some(boundary) = get_lock_boundary(conn)? {
+        if DateTime::parse_from_rfc3339(start)? < boundary {
+            anyhow::bail!(
+                "Sessions up to {} are locked. Unlock them with 'walrus unlock'",
+                settings::date(locked_until(boundary))
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Returns the start of the first unlocked day, if there is a period lock.
+pub fn get_lock_boundary(conn: &Connection) -> Result<Option<DateTime<chrono::FixedOffset>>> {
+    let until: Option<String> = conn.query_row("SELECT until FROM period_lock", [], |row| row.get(0)).optional()?;
+    until.map(|u| DateTime::parse_from_rfc3339(&u).map_err(Into::into)).transpose()
+}
+
+/// The last locked day for a lock boundary.
+pub fn locked_until(boundary: DateTime<chrono::FixedOffset>) -> chrono::NaiveDate {
+    boundary.date_naive().pred_opt().unwrap()
+}
+
+/// Locks every session started on or before `until`, replacing any
+/// previous period lock.
+pub fn set_period_lock(conn: &Connection, until: chrono::NaiveDate) -> Result<()> {
+    let boundary = to_rfc3339(until.succ_opt().unwrap().and_hms_opt(0, 0, 0).unwrap())?;
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM period_lock", [])?;
+    tx.execute("INSERT INTO period_lock (until) VALUES (?1)", [&boundary])?;
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn remove_period_lock(conn: &Connection) -> Result<bool> {
+    Ok(conn.execute("DELETE FROM period_lock", [])? > 0)
+}
+
+/// Sets the lock flag of a session. Returns false if there is no such
+/// session outside the trash.
+pub fn set_session_locked(conn: &Connection, id: i64, locked: bool) -> Result<bool> {
+    let rows = conn.execute(
+        "UPDATE sessions SET locked = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+        rusqlite::params![locked, id],
+    )?;
+    Ok(rows > 0)
+}
+
+pub fn count_locked_sessions(conn: &Connection) -> Result<usize> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sessions WHERE locked = 1 AND deleted_at IS NULL",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count as usize)
+}
+
 pub fn update_session_topic(conn: &Connection, id: i64, topic: &str) -> Result<()> {
+    ensure_unlocked(conn, id)?;
+    let before = journal::session_image(conn, id)?;
     conn.execute("UPDATE sessions SET topic = ?1 WHERE id = ?2", rusqlite::params![topic, id])?;
+    journal::record_change(conn, id, before, journal::session_image(conn, id)?)?;
     Ok(())
 }
 
 pub fn update_session_start(conn: &Connection, id: i64, start: &str) -> Result<()> {
+    ensure_unlocked(conn, id)?;
+    ensure_period_unlocked(conn, start)?;
+    let before = journal::session_image(conn, id)?;
     conn.execute("UPDATE sessions SET start_time = ?1 WHERE id = ?2", rusqlite::params![start, id])?;
+    journal::record_change(conn, id, before, journal::session_image(conn, id)?)?;
     Ok(())
 }
 
 pub fn update_session_end(conn: &Connection, id: i64, end: &str) -> Result<()> {
+    ensure_unlocked(conn, id)?;
+    let before = journal::session_image(conn, id)?;
     conn.execute("UPDATE sessions SET end_time = ?1 WHERE id = ?2", rusqlite::params![end, id])?;
+    journal::record_change(conn, id, before, journal::session_image(conn, id)?)?;
     Ok(())
 }
 
 pub fn insert_session(conn: &Connection, topic: &str, start: &str, end: &str) -> Result<()> {
+    ensure_period_unlocked(conn, start)?;
     conn.execute(
         "INSERT INTO sessions (topic, start_time, end_time) VALUES (?1, ?2, ?3)",
         rusqlite::params![topic, start, end],
     )?;
+    let id = conn.last_insert_rowid();
+    journal::record_change(conn, id, None, journal::session_image(conn, id)?)?;
     Ok(())
 }
 
 pub fn parse_datetime(s: &str) -> Result<String> {
-    let dt = NaiveDateTime::parse_from_str(s, "%d.%m.%Y %H:%M")
-        .map_err(|_| anyhow::anyhow!("Invalid datetime format. Use DD.MM.YYYY HH:MM"))?;
+    let dt = parse_naive_datetime(s)
+        .ok_or_else(|| anyhow::anyhow!("Invalid datetime format. Use DD.MM.YYYY HH:MM"))?;
+
+    to_rfc3339(dt)
+}
+
+/// Date formats accepted in arguments: the configured one first, then the
+/// built-in ones.
+fn date_formats() -> [&'static str; 3] {
+    [settings::get().input_date_format.as_str(), "%d.%m.%Y", "%Y-%m-%d"]
+}
+
+fn parse_naive_date(s: &str) -> Option<chrono::NaiveDate> {
+    date_formats().iter().find_map(|f| chrono::NaiveDate::parse_from_str(s, f).ok())
+}
+
+/// A date in one of the accepted formats followed by `HH:MM`.
+pub fn parse_naive_datetime(s: &str) -> Option<NaiveDateTime> {
+    let (date, time) = s.trim().rsplit_once(' ')?;
+    let time = chrono::NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+    Some(parse_naive_date(date.trim())?.and_time(time))
+}
 
+pub fn to_rfc3339(dt: NaiveDateTime) -> Result<String> {
     let local_dt = Local.from_local_datetime(&dt).single()
         .ok_or_else(|| anyhow::anyhow!("Ambiguous datetime"))?;
 
     Ok(local_dt.to_rfc3339())
 }
 
-pub fn get_sessions_with_calculated_hours(conn: &Connection, limit: usize) -> Result<Vec<(Session, f64)>> {
-    let mut stmt = conn.prepare(
+/// Parses a plain date, either `DD.MM.YYYY` or `YYYY-MM-DD`.
+pub fn parse_date(s: &str) -> Result<chrono::NaiveDate> {
+    parse_naive_date(s)
+        .ok_or_else(|| anyhow::anyhow!("Invalid date '{}'. Use DD.MM.YYYY or YYYY-MM-DD", s))
+}
+
+/// Parses a date (`DD.MM.YYYY`, meaning midnight) or a full datetime.
+/// The flag tells whether only a date was given.
+pub fn parse_date_or_datetime(s: &str) -> Result<(NaiveDateTime, bool)> {
+    if let Some(date) = parse_naive_date(s) {
+        return Ok((date.and_hms_opt(0, 0, 0).unwrap(), true));
+    }
+
+    parse_naive_datetime(s)
+        .map(|dt| (dt, false))
+        .ok_or_else(|| anyhow::anyhow!("Invalid date '{}'. Use DD.MM.YYYY or DD.MM.YYYY HH:MM", s))
+}
+
+/// Parses a session id or an inclusive id range like `12-20`.
+pub fn parse_id_range(s: &str) -> Result<(i64, i64)> {
+    let invalid = || anyhow::anyhow!("Invalid id '{}'. Use e.g. 12 or 12-20", s);
+
+    match s.split_once('-') {
+        Some((lo, hi)) => {
+            let lo: i64 = lo.trim().parse().map_err(|_| invalid())?;
+            let hi: i64 = hi.trim().parse().map_err(|_| invalid())?;
+            if hi < lo {
+                anyhow::bail!("Invalid id range '{}': end is before start", s);
+            }
+            Ok((lo, hi))
+        }
+        None => {
+            let id: i64 = s.trim().parse().map_err(|_| invalid())?;
+            Ok((id, id))
+        }
+    }
+}
+
+/// Parses an age like `30d`, `2w` or `12h`.
+pub fn parse_age(s: &str) -> Result<chrono::Duration> {
+    let invalid = || anyhow::anyhow!("Invalid age '{}'. Use e.g. 12h, 30d or 2w", s);
+
+    let trimmed = s.trim();
+    let unit = trimmed.chars().last().ok_or_else(invalid)?;
+    let number: i64 = trimmed[..trimmed.len() - unit.len_utf8()].parse().map_err(|_| invalid())?;
+    if number <= 0 {
+        anyhow::bail!("Invalid age '{}'. It must be more than zero", s);
+    }
+
+    match unit {
+        'h' => Ok(chrono::Duration::hours(number)),
+        'd' => Ok(chrono::Duration::days(number)),
+        'w' => Ok(chrono::Duration::weeks(number)),
+        _ => Err(invalid()),
+    }
+}
+
+pub fn get_sessions_with_calculated_hours(conn: &Connection, limit: usize, topics: &TopicFilter) -> Result<Vec<(Session, f64)>> {
+    let mut params = Vec::new();
+    let topic_filter = topic_clause(topics, &mut params)
+        .map(|c| format!("AND {}", c))
+        .unwrap_or_default();
+    params.push((limit as i64).into());
+
+    let mut stmt = conn.prepare(&format!(
         "SELECT id, topic, start_time, end_time,
                 CASE WHEN end_time IS NOT NULL
                      THEN (julianday(end_time) - julianday(start_time)) * 24
-                     ELSE 0.0 END as hours
+                     ELSE 0.0 END as hours,
+                {}
          FROM sessions
+         WHERE deleted_at IS NULL {}
          ORDER BY start_time DESC
-         LIMIT ?1"
-    )?;
+         LIMIT ?",
+        LOCKED, topic_filter
+    ))?;
 
-    let sessions = stmt.query_map([limit], |row| {
+    let sessions = stmt.query_map(rusqlite::params_from_iter(params), |row| {
         let id: i64 = row.get(0)?;
         let topic: String = row.get(1)?;
         let start_str: String = row.get(2)?;
         let end_str: Option<String> = row.get(3)?;
         let hours: f64 = row.get(4)?;
-        Ok((id, topic, start_str, end_str, hours))
+        let locked: bool = row.get(5)?;
+        Ok((id, topic, start_str, end_str, hours, locked))
     })?;
 
     let mut result = Vec::new();
     for session in sessions {
-        let (id, topic, start_str, end_str, hours) = session?;
+        let (id, topic, start_str, end_str, hours, locked) = session?;
         let start = DateTime::parse_from_rfc3339(&start_str)?;
         let end = end_str.map(|s| DateTime::parse_from_rfc3339(&s)).transpose()?;
-        result.push((Session { id, topic, start, end }, hours));
+        result.push((Session { id, topic, start, end, locked }, hours));
     }
 
     Ok(result)
-}
\ No newline at end of file
+}
+
+pub fn get_matching_sessions(conn: &Connection, filter: &SessionFilter) -> Result<Vec<(Session, f64)>> {
+    let (where_clause, params) = filter.where_clause();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, topic, start_time, end_time,
+                CASE WHEN end_time IS NOT NULL
+                     THEN (julianday(end_time) - julianday(start_time)) * 24
+                     ELSE 0.0 END as hours,
+                {}
+         FROM sessions
+         WHERE {}
+         ORDER BY start_time ASC",
+        LOCKED, where_clause
+    ))?;
+
+    let sessions = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+        let id: i64 = row.get(0)?;
+        let topic: String = row.get(1)?;
+        let start_str: String = row.get(2)?;
+        let end_str: Option<String> = row.get(3)?;
+        let hours: f64 = row.get(4)?;
+        let locked: bool = row.get(5)?;
+        Ok((id, topic, start_str, end_str, hours, locked))
+    })?;
+
+    let mut result = Vec::new();
+    for session in sessions {
+        let (id, topic, start_str, end_str, hours, locked) = session?;
+        let start = DateTime::parse_from_rfc3339(&start_str)?;
+        let end = end_str.map(|s| DateTime::parse_from_rfc3339(&s)).transpose()?;
+        result.push((Session { id, topic, start, end, locked }, hours));
+    }
+
+    Ok(result)
+}
+
+pub struct TopicUsage {
+    pub topic: String,
+    pub sessions: usize,
+    pub active: usize,
+    pub hours: f64,
+}
+
+/// Every topic with its number of sessions, most recently used first.
+pub fn get_all_topics(conn: &Connection) -> Result<Vec<(String, usize)>> {
+    let mut stmt = conn.prepare(
+        "SELECT topic, COUNT(*) FROM sessions
+         WHERE deleted_at IS NULL AND topic IS NOT NULL
+         GROUP BY topic
+         ORDER BY MAX(julianday(start_time)) DESC"
+    )?;
+
+    let topics = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize)))?;
+    let result: Result<Vec<(String, usize)>, _> = topics.collect();
+    result.map_err(Into::into)
+}
+
+/// Finds every spelling of the given topics (matched case-insensitively),
+/// including sessions in the trash.
+pub fn get_topic_usage(conn: &Connection, topics: &[String]) -> Result<Vec<TopicUsage>> {
+    let placeholders = vec!["?"; topics.len()].join(", ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT topic, COUNT(*),
+                SUM(CASE WHEN end_time IS NULL AND deleted_at IS NULL THEN 1 ELSE 0 END),
+                SUM(CASE WHEN end_time IS NOT NULL
+                         THEN (julianday(end_time) - julianday(start_time)) * 24
+                         ELSE 0.0 END)
+         FROM sessions
+         WHERE topic COLLATE NOCASE IN ({})
+         GROUP BY topic
+         ORDER BY topic",
+        placeholders
+    ))?;
+
+    let usage = stmt.query_map(rusqlite::params_from_iter(topics), |row| {
+        Ok(TopicUsage {
+            topic: row.get(0)?,
+            sessions: row.get::<_, i64>(1)? as usize,
+            active: row.get::<_, i64>(2)? as usize,
+            hours: row.get(3)?,
+        })
+    })?;
+
+    let result: Result<Vec<TopicUsage>, _> = usage.collect();
+    result.map_err(Into::into)
+}
+
+/// Renames every session whose topic is exactly `from`, as spelled in the
+/// database. Returns the number of sessions changed.
+pub fn rename_topic(conn: &Connection, from: &str, to: &str) -> Result<usize> {
+    let ids = conn.prepare("SELECT id FROM sessions WHERE topic = ?1 AND topic != ?2")?
+        .query_map([from, to], |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for &id in &ids {
+        update_session_topic(conn, id, to)?;
+    }
+
+    Ok(ids.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ids_and_ranges() {
+        assert_eq!(parse_id_range("12").unwrap(), (12, 12));
+        assert_eq!(parse_id_range("12-20").unwrap(), (12, 20));
+        assert_eq!(parse_id_range(" 3 - 5 ").unwrap(), (3, 5));
+        assert!(parse_id_range("20-12").is_err());
+        assert!(parse_id_range("12-").is_err());
+        assert!(parse_id_range("abc").is_err());
+    }
+
+    #[test]
+    fn parses_ages() {
+        assert_eq!(parse_age("12h").unwrap(), chrono::Duration::hours(12));
+        assert_eq!(parse_age("30d").unwrap(), chrono::Duration::days(30));
+        assert_eq!(parse_age("2w").unwrap(), chrono::Duration::weeks(2));
+        assert!(parse_age("0d").is_err());
+        assert!(parse_age("-5d").is_err());
+        assert!(parse_age("6m").is_err());
+        assert!(parse_age("").is_err());
+    }
+}
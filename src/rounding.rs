@@ -0,0 +1,153 @@
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundingMode {
+    Up,
+    Down,
+    Nearest,
+}
+
+/// Whether each session is rounded on its own, or all sessions of a day
+/// (per topic or invoice line) are added up first.
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum RoundingScope {
+    Session,
+    Day,
+}
+
+/// How tracked time is rounded for reports and billing. The stored sessions
+/// are never changed. An increment of 0 turns rounding off.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rounding {
+    pub mode: RoundingMode,
+    /// Minutes
+    pub increment: u32,
+    pub per: RoundingScope,
+}
+
+impl Default for Rounding {
+    fn default() -> Self {
+        Rounding { mode: RoundingMode::Nearest, increment: 0, per: RoundingScope::Session }
+    }
+}
+
+// Durations come out of julianday arithmetic, so a quarter hour may be a hair
+// above or below 0.25. Don't let that push a value into the next increment.
+const EPSILON: f64 = 1e-6;
+
+impl Rounding {
+    pub fn is_active(&self) -> bool {
+        self.increment > 0
+    }
+
+    pub fn round(&self, hours: f64) -> f64 {
+        if !self.is_active() {
+            return hours;
+        }
+
+        let step = self.increment as f64 / 60.0;
+        let units = hours / step;
+        let units = match self.mode {
+            RoundingMode::Up => (units - EPSILON).ceil(),
+            RoundingMode::Down => (units + EPSILON).floor(),
+            RoundingMode::Nearest => units.round(),
+        };
+        units.max(0.0) * step
+    }
+
+    /// Rounds each entry. With per-day rounding, entries with the same key on
+    /// the same day are rounded as one and the result is shared out in
+    /// proportion to their raw hours.
+    pub fn apply<K: PartialEq>(&self, entries: &[(K, NaiveDate, f64)]) -> Vec<f64> {
+        match self.per {
+            RoundingScope::Session => entries.iter().map(|(_, _, hours)| self.round(*hours)).collect(),
+            RoundingScope::Day => entries.iter()
+                .map(|(key, day, hours)| {
+                    let raw: f64 = entries.iter()
+                        .filter(|(k, d, _)| k == key && d == day)
+                        .fold(0.0, |sum, (_, _, h)| sum + h);
+                    if raw > 0.0 { hours * self.round(raw) / raw } else { 0.0 }
+                })
+                .collect(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        if !self.is_active() {
+            return "none".to_string();
+        }
+        let mode = match self.mode {
+            RoundingMode::Up => "up to",
+            RoundingMode::Down => "down to",
+            RoundingMode::Nearest => "to the nearest",
+        };
+        let per = match self.per {
+            RoundingScope::Session => "session",
+            RoundingScope::Day => "day",
+        };
+        format!("{} {} min per {}", mode, self.increment, per)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rounding(mode: RoundingMode, increment: u32, per: RoundingScope) -> Rounding {
+        Rounding { mode, increment, per }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn rounds_to_the_increment() {
+        let up = rounding(RoundingMode::Up, 15, RoundingScope::Session);
+        let down = rounding(RoundingMode::Down, 15, RoundingScope::Session);
+        let nearest = rounding(RoundingMode::Nearest, 15, RoundingScope::Session);
+
+        assert_close(up.round(0.26), 0.5);
+        assert_close(down.round(0.49), 0.25);
+        assert_close(nearest.round(0.37), 0.25);
+        assert_close(nearest.round(0.38), 0.5);
+        assert_close(up.round(0.0), 0.0);
+    }
+
+    #[test]
+    fn ignores_floating_point_noise() {
+        let up = rounding(RoundingMode::Up, 15, RoundingScope::Session);
+        let down = rounding(RoundingMode::Down, 15, RoundingScope::Session);
+
+        assert_close(up.round(0.25 + 1e-9), 0.25);
+        assert_close(down.round(0.5 - 1e-9), 0.5);
+    }
+
+    #[test]
+    fn increment_zero_leaves_hours_alone() {
+        let off = Rounding::default();
+        assert!(!off.is_active());
+        assert_close(off.round(0.123), 0.123);
+    }
+
+    #[test]
+    fn per_day_rounds_the_sum_and_shares_it_out() {
+        let day = NaiveDate::from_ymd_opt(2026, 10, 1).unwrap();
+        let next = NaiveDate::from_ymd_opt(2026, 10, 2).unwrap();
+        let entries = [("a", day, 0.1), ("a", day, 0.3), ("b", day, 0.1), ("a", next, 0.1)];
+
+        let per_day = rounding(RoundingMode::Up, 15, RoundingScope::Day).apply(&entries);
+        assert_close(per_day[0], 0.125);
+        assert_close(per_day[1], 0.375);
+        assert_close(per_day[2], 0.25);
+        assert_close(per_day[3], 0.25);
+
+        let per_session = rounding(RoundingMode::Up, 15, RoundingScope::Session).apply(&entries);
+        assert_close(per_session.iter().sum(), 1.25);
+    }
+}
@@ -0,0 +1,137 @@
+use rusqlite::Connection;
+use anyhow::Result;
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime};
+use crate::absences::{self, Absence};
+use crate::{periods, queries, Period};
+
+/// Expected hours per weekday (Monday first), valid from `effective_from`
+/// until a later schedule takes over.
+pub struct Schedule {
+    pub id: i64,
+    pub effective_from: NaiveDate,
+    pub hours: [f64; 7],
+}
+
+impl Schedule {
+    pub fn weekly_hours(&self) -> f64 {
+        self.hours.iter().sum()
+    }
+}
+
+/// Expected and tracked hours of one period, with the overtime balance
+/// accumulated up to its end.
+pub struct BalanceRow {
+    pub label: String,
+    pub expected: f64,
+    pub worked: f64,
+    /// Days off, half days count as 0.5
+    pub days_off: f64,
+    pub balance: f64,
+}
+
+/// Sets the schedule starting on `from`, replacing one for the same day.
+pub fn set_schedule(conn: &Connection, from: NaiveDate, hours: [f64; 7]) -> Result<()> {
+    let from = from.format("%Y-%m-%d").to_string();
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM schedules WHERE effective_from = ?1", [&from])?;
+    tx.execute(
+        "INSERT INTO schedules (effective_from, monday, tuesday, wednesday, thursday, friday, saturday, sunday)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![from, hours[0], hours[1], hours[2], hours[3], hours[4], hours[5], hours[6]],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// All schedules, oldest first.
+pub fn get_schedules(conn: &Connection) -> Result<Vec<Schedule>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, effective_from, monday, tuesday, wednesday, thursday, friday, saturday, sunday
+         FROM schedules
+         ORDER BY effective_from"
+    )?;
+
+    let schedules = stmt.query_map([], |row| {
+        let mut hours = [0.0; 7];
+        for (i, h) in hours.iter_mut().enumerate() {
+            *h = row.get(i + 2)?;
+        }
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, hours))
+    })?;
+
+    let mut result = Vec::new();
+    for schedule in schedules {
+        let (id, from, hours) = schedule?;
+        result.push(Schedule {
+            id,
+            effective_from: NaiveDate::parse_from_str(&from, "%Y-%m-%d")?,
+            hours,
+        });
+    }
+
+    Ok(result)
+}
+
+pub fn remove_schedule(conn: &Connection, id: i64) -> Result<bool> {
+    let rows = conn.execute("DELETE FROM schedules WHERE id = ?1", [id])?;
+    Ok(rows > 0)
+}
+
+/// Hours expected on a day, 0 before the first schedule starts and reduced
+/// by a day off.
+pub fn expected_hours(schedules: &[Schedule], absences: &[Absence], day: NaiveDate) -> f64 {
+    let hours = schedules.iter()
+        .rev()
+        .find(|s| s.effective_from <= day)
+        .map(|s| s.hours[day.weekday().num_days_from_monday() as usize])
+        .unwrap_or(0.0);
+    let off = absences.iter().find(|a| a.day == day).map(|a| a.fraction()).unwrap_or(0.0);
+    hours * (1.0 - off)
+}
+
+/// Hours expected from `from` up to, but not including, `to`.
+pub fn expected_between(schedules: &[Schedule], absences: &[Absence], from: NaiveDate, to: NaiveDate) -> f64 {
+    from.iter_days()
+        .take_while(|day| *day < to)
+        .fold(0.0, |sum, day| sum + expected_hours(schedules, absences, day))
+}
+
+/// Walks all periods since the first schedule started and compares tracked
+/// with expected hours. The current period counts today as a full day.
+pub fn balance(conn: &Connection, schedules: &[Schedule], period: Period, now: NaiveDateTime) -> Result<Vec<BalanceRow>> {
+    let Some(first) = schedules.first() else {
+        return Ok(Vec::new());
+    };
+    let since = first.effective_from.and_hms_opt(0, 0, 0).unwrap();
+    let absences = absences::get_absences(conn, first.effective_from, now.date())?;
+
+    let mut rows = Vec::new();
+    for back in 0.. {
+        let range = periods::range(period, back, now);
+        if range.end <= since {
+            break;
+        }
+
+        let start = range.start.max(since);
+        let until = if back == 0 { now.date() + Days::new(1) } else { range.end.date() };
+        let expected = expected_between(schedules, &absences, start.date(), until);
+        let days_off = absences.iter()
+            .filter(|a| a.day >= start.date() && a.day < until)
+            .filter(|a| expected_hours(schedules, &[], a.day) > 0.0)
+            .fold(0.0, |sum, a| sum + a.fraction());
+        let worked = queries::get_period_stats(conn, start, range.end, &queries::TopicFilter::default())?
+            .iter()
+            .fold(0.0, |sum, (_, hours)| sum + hours);
+
+        rows.push(BalanceRow { label: range.label, expected, worked, days_off, balance: 0.0 });
+    }
+
+    rows.reverse();
+    let mut balance = 0.0;
+    for row in &mut rows {
+        balance += row.worked - row.expected;
+        row.balance = balance;
+    }
+
+    Ok(rows)
+}
@@ -0,0 +1,200 @@
+// Preferences that shape how dates and durations are read and printed. They
+// are resolved once at startup from the config file and command line flags,
+// so the display and parsing helpers don't need the config passed along.
+
+use anyhow::Result;
+use chrono::format::StrftimeItems;
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Weekday};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use crate::config::Config;
+
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorChoice {
+    /// Only when writing to a terminal and NO_COLOR is not set
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Charset {
+    /// Unicode if the locale is UTF-8
+    Auto,
+    Unicode,
+    Ascii,
+}
+
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DurationStyle {
+    /// 1.50h
+    Decimal,
+    /// 1:30
+    Clock,
+}
+
+pub struct Settings {
+    pub date_format: String,
+    pub time_format: String,
+    pub input_date_format: String,
+    pub duration: DurationStyle,
+    pub color: bool,
+    pub week_start: Weekday,
+    /// 1 (January) to 12
+    pub fiscal_year_start: u32,
+    /// Whether rules and block characters can be drawn, otherwise tables and
+    /// charts use ASCII
+    pub unicode: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            date_format: "%d.%m.%Y".to_string(),
+            time_format: "%H:%M".to_string(),
+            input_date_format: "%d.%m.%Y".to_string(),
+            duration: DurationStyle::Decimal,
+            color: false,
+            week_start: Weekday::Mon,
+            fiscal_year_start: 1,
+            unicode: true,
+        }
+    }
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+fn check_format(key: &str, format: &str) -> Result<()> {
+    StrftimeItems::new(format).parse()
+        .map(|_| ())
+        .map_err(|_| anyhow::anyhow!("Invalid date format '{}' for {}", format, key))
+}
+
+/// Checks the config and turns it into settings. `color` overrides the
+/// configured color choice.
+pub fn from_config(config: &Config, color: Option<ColorChoice>) -> Result<Settings> {
+    use std::io::IsTerminal;
+
+    check_format("display.date_format", &config.display.date_format)?;
+    check_format("display.time_format", &config.display.time_format)?;
+    check_format("input.date_format", &config.input.date_format)?;
+
+    let week_start = config.calendar.week_start.parse::<Weekday>()
+        .map_err(|_| anyhow::anyhow!("Invalid calendar.week_start '{}'. Use a weekday like monday", config.calendar.week_start))?;
+
+    let fiscal_year_start = config.calendar.fiscal_year_start;
+    if !(1..=12).contains(&fiscal_year_start) {
+        anyhow::bail!("Invalid calendar.fiscal_year_start {}. Use a month from 1 to 12", fiscal_year_start);
+    }
+
+    let color = match color.unwrap_or(config.display.color) {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+    };
+
+    Ok(Settings {
+        date_format: config.display.date_format.clone(),
+        time_format: config.display.time_format.clone(),
+        input_date_format: config.input.date_format.clone(),
+        duration: config.display.duration,
+        color,
+        week_start,
+        fiscal_year_start,
+        unicode: match config.display.charset {
+            Charset::Auto => unicode_locale(),
+            Charset::Unicode => true,
+            Charset::Ascii => false,
+        },
+    })
+}
+
+/// Whether the locale says the terminal speaks UTF-8.
+fn unicode_locale() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"].iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+        .map(|locale| {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+        .unwrap_or(false)
+}
+
+pub fn init(settings: Settings) {
+    let _ = SETTINGS.set(settings);
+}
+
+pub fn get() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
+
+pub fn date(date: NaiveDate) -> String {
+    date.format(&get().date_format).to_string()
+}
+
+/// The date with its short weekday name in front, e.g. `Mon 06.01.2025`.
+pub fn day(date: NaiveDate) -> String {
+    format!("{} {}", date.format("%a"), self::date(date))
+}
+
+pub fn datetime(dt: &DateTime<FixedOffset>) -> String {
+    naive_datetime(&dt.naive_local())
+}
+
+pub fn naive_datetime(dt: &NaiveDateTime) -> String {
+    let settings = get();
+    format!("{} {}", dt.format(&settings.date_format), dt.format(&settings.time_format))
+}
+
+/// Hours as `1.50h` or `1:30`, depending on the configured style.
+pub fn hours(hours: f64) -> String {
+    match get().duration {
+        DurationStyle::Decimal => format!("{:.2}h", hours),
+        DurationStyle::Clock => {
+            let minutes = (hours.abs() * 60.0).round() as i64;
+            let sign = if hours < 0.0 && minutes > 0 { "-" } else { "" };
+            format!("{}{}:{:02}", sign, minutes / 60, minutes % 60)
+        }
+    }
+}
+
+/// Columns of the terminal, or 80 when not writing to one.
+pub fn terminal_width() -> usize {
+    use std::io::IsTerminal;
+
+    // size() asks the controlling terminal, which is still there when piped
+    if !std::io::stdout().is_terminal() {
+        return 80;
+    }
+    ratatui::crossterm::terminal::size()
+        .map(|(columns, _)| columns as usize)
+        .ok()
+        .or_else(|| std::env::var("COLUMNS").ok()?.parse().ok())
+        .unwrap_or(80)
+}
+
+pub enum Color {
+    Green,
+    Red,
+    Yellow,
+    Dim,
+}
+
+/// Wraps text in ANSI color codes if colors are on. Pad before painting,
+/// the escape codes would count towards the width.
+pub fn paint(text: &str, color: Color) -> String {
+    if !get().color {
+        return text.to_string();
+    }
+    let code = match color {
+        Color::Green => "32",
+        Color::Red => "31",
+        Color::Yellow => "33",
+        Color::Dim => "2",
+    };
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
@@ -0,0 +1,114 @@
+// Forgotten timers: running sessions that are older than the configured
+// threshold or have run past the end of the day.
+
+use rusqlite::Connection;
+use anyhow::Result;
+use chrono::{DateTime, Days, Local, NaiveDateTime, NaiveTime};
+use crate::config::StaleConfig;
+use crate::queries::{self, Session};
+use crate::settings;
+
+pub struct StaleSession {
+    pub session: Session,
+    pub hours: f64,
+    /// The end of day the session ran past, if that is why it is stale
+    pub end_of_day: Option<NaiveDateTime>,
+    /// When another session was last stopped while this one ran
+    pub last_activity: Option<NaiveDateTime>,
+}
+
+impl StaleSession {
+    fn start(&self) -> NaiveDateTime {
+        self.session.start.with_timezone(&Local).naive_local()
+    }
+
+    /// Where to cap the session if nothing else is asked for: the end of day
+    /// it ran past, otherwise after the threshold.
+    pub fn default_end(&self, threshold: chrono::Duration) -> NaiveDateTime {
+        self.end_of_day.unwrap_or_else(|| self.start() + threshold)
+    }
+
+    /// Parses a cap time, either `HH:MM` on the day the session started or a
+    /// full `DD.MM.YYYY HH:MM`.
+    pub fn parse_end(&self, s: &str) -> Result<NaiveDateTime> {
+        let s = s.trim();
+        let end = match NaiveTime::parse_from_str(s, "%H:%M") {
+            Ok(time) => self.start().date().and_time(time),
+            Err(_) => queries::parse_naive_datetime(s)
+                .ok_or_else(|| anyhow::anyhow!("Invalid time '{}'. Use HH:MM or DD.MM.YYYY HH:MM", s))?,
+        };
+        self.check_end(end)?;
+        Ok(end)
+    }
+
+    pub fn check_end(&self, end: NaiveDateTime) -> Result<()> {
+        if end <= self.start() {
+            anyhow::bail!("{} is not after the session start", settings::naive_datetime(&end));
+        }
+        if end > Local::now().naive_local() {
+            anyhow::bail!("{} is in the future", settings::naive_datetime(&end));
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_threshold(config: &StaleConfig) -> Result<chrono::Duration> {
+    queries::parse_age(&config.threshold)
+        .map_err(|e| anyhow::anyhow!("Invalid stale threshold in config: {}", e))
+}
+
+/// Checks the threshold and end of day can be read.
+pub fn check(config: &StaleConfig) -> Result<()> {
+    parse_threshold(config)?;
+    parse_end_of_day(config)?;
+    Ok(())
+}
+
+fn parse_end_of_day(config: &StaleConfig) -> Result<Option<NaiveTime>> {
+    config.end_of_day.as_deref()
+        .map(|s| NaiveTime::parse_from_str(s, "%H:%M")
+            .map_err(|_| anyhow::anyhow!("Invalid end_of_day '{}' in config. Use HH:MM", s)))
+        .transpose()
+}
+
+/// The first end of day after `start`.
+fn next_end_of_day(start: NaiveDateTime, end_of_day: NaiveTime) -> NaiveDateTime {
+    let same_day = start.date().and_time(end_of_day);
+    if same_day > start {
+        same_day
+    } else {
+        same_day + Days::new(1)
+    }
+}
+
+/// Running sessions that are over the threshold or past an end of day.
+/// Locked sessions are left out, since they can't be capped.
+pub fn find(conn: &Connection, config: &StaleConfig, now: DateTime<Local>) -> Result<Vec<StaleSession>> {
+    let threshold = parse_threshold(config)?;
+    let end_of_day = parse_end_of_day(config)?;
+    let now_local = now.naive_local();
+
+    let mut stale = Vec::new();
+    for session in queries::get_active_sessions(conn)?.into_iter().filter(|s| !s.locked) {
+        let start = session.start.with_timezone(&Local).naive_local();
+        let running = now_local - start;
+        let past_end_of_day = end_of_day
+            .map(|eod| next_end_of_day(start, eod))
+            .filter(|eod| *eod <= now_local);
+
+        if running <= threshold && past_end_of_day.is_none() {
+            continue;
+        }
+
+        let last_activity = queries::get_last_activity(conn, session.id, &session.start)?
+            .map(|t| t.with_timezone(&Local).naive_local());
+        stale.push(StaleSession {
+            hours: running.num_seconds() as f64 / 3600.0,
+            session,
+            end_of_day: past_end_of_day,
+            last_activity,
+        });
+    }
+
+    Ok(stale)
+}
@@ -0,0 +1,111 @@
+use rusqlite::Connection;
+use anyhow::Result;
+use clap::ValueEnum;
+use crate::Period;
+use crate::topics;
+
+/// Hours expected per day, week, month, quarter or year, either for all tracked time
+/// or for the topics matching a pattern.
+pub struct Target {
+    pub id: i64,
+    pub topic: Option<String>,
+    pub period: Period,
+    pub hours: f64,
+}
+
+/// Tracked hours measured against a target.
+pub struct Progress {
+    pub label: String,
+    pub actual: f64,
+    pub target: f64,
+}
+
+impl Progress {
+    pub fn remaining(&self) -> f64 {
+        (self.target - self.actual).max(0.0)
+    }
+
+    /// Positive when over the target, negative when under
+    pub fn difference(&self) -> f64 {
+        self.actual - self.target
+    }
+}
+
+pub fn period_name(period: &Period) -> &'static str {
+    match period {
+        Period::Day => "day",
+        Period::Week => "week",
+        Period::Month => "month",
+        Period::Quarter => "quarter",
+        Period::Year => "year",
+    }
+}
+
+/// Sets the target for a topic pattern (or all time) and period, replacing an
+/// existing one for the same scope.
+pub fn set_target(conn: &Connection, topic: Option<&str>, period: &Period, hours: f64) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "DELETE FROM targets WHERE topic IS ?1 AND period = ?2",
+        rusqlite::params![topic, period_name(period)],
+    )?;
+    tx.execute(
+        "INSERT INTO targets (topic, period, hours) VALUES (?1, ?2, ?3)",
+        rusqlite::params![topic, period_name(period), hours],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn get_targets(conn: &Connection) -> Result<Vec<Target>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, topic, period, hours FROM targets
+         ORDER BY CASE period WHEN 'day' THEN 0 WHEN 'week' THEN 1 WHEN 'month' THEN 2 WHEN 'quarter' THEN 3 ELSE 4 END,
+                  topic IS NOT NULL, topic"
+    )?;
+
+    let targets = stmt.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, String>(2)?, row.get::<_, f64>(3)?))
+    })?;
+
+    let mut result = Vec::new();
+    for target in targets {
+        let (id, topic, period, hours) = target?;
+        let period = Period::from_str(&period, true)
+            .map_err(|_| anyhow::anyhow!("Invalid period '{}' for target {}", period, id))?;
+        result.push(Target { id, topic, period, hours });
+    }
+
+    Ok(result)
+}
+
+pub fn remove_target(conn: &Connection, id: i64) -> Result<bool> {
+    let rows = conn.execute("DELETE FROM targets WHERE id = ?1", [id])?;
+    Ok(rows > 0)
+}
+
+/// Measures the per-topic totals of one period against the targets set for
+/// that kind of period. The overall target is skipped when `overall` is false,
+/// e.g. because the totals only cover some topics.
+pub fn progress(
+    targets: &[Target],
+    period: &Period,
+    topics: &[(String, f64)],
+    separator: &str,
+    overall: bool,
+) -> Vec<Progress> {
+    targets.iter()
+        .filter(|t| t.period == *period)
+        .filter(|t| overall || t.topic.is_some())
+        .map(|t| {
+            let actual = topics.iter()
+                .filter(|(topic, _)| t.topic.as_deref().is_none_or(|p| topics::matches(p, topic, separator)))
+                .fold(0.0, |sum, (_, hours)| sum + hours);
+            Progress {
+                label: t.topic.clone().unwrap_or_else(|| "Total".to_string()),
+                actual,
+                target: t.hours,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,134 @@
+// Hours per topic and day for one week or month, laid out as a grid with
+// topics as rows and days as columns.
+
+use rusqlite::Connection;
+use anyhow::Result;
+use chrono::NaiveDate;
+use crate::periods::Range;
+use crate::queries::{self, TopicFilter};
+use crate::settings;
+
+pub struct Timesheet {
+    pub label: String,
+    pub days: Vec<NaiveDate>,
+    /// Hours per topic, one entry per day in `days`
+    pub rows: Vec<(String, Vec<f64>)>,
+}
+
+impl Timesheet {
+    pub fn day_totals(&self) -> Vec<f64> {
+        (0..self.days.len())
+            .map(|i| self.rows.iter().fold(0.0, |sum, (_, hours)| sum + hours[i]))
+            .collect()
+    }
+
+    pub fn total(&self) -> f64 {
+        self.rows.iter().fold(0.0, |sum, (_, hours)| sum + row_total(hours))
+    }
+
+    /// Column heading for a day: weekday and date for a week, only the day
+    /// of the month for longer sheets.
+    pub fn heading(&self, day: NaiveDate) -> String {
+        if self.days.len() <= 7 {
+            day.format("%a %d").to_string()
+        } else {
+            day.format("%d").to_string()
+        }
+    }
+}
+
+pub fn row_total(hours: &[f64]) -> f64 {
+    hours.iter().fold(0.0, |sum, h| sum + h)
+}
+
+/// Collects the finished sessions of the range into a timesheet, topics
+/// sorted by name.
+pub fn build(conn: &Connection, range: &Range, topics: &TopicFilter) -> Result<Timesheet> {
+    let days: Vec<NaiveDate> = range.start.date()
+        .iter_days()
+        .take_while(|day| *day <= range.last_day)
+        .collect();
+
+    let mut rows: Vec<(String, Vec<f64>)> = Vec::new();
+    for (topic, day, hours) in queries::get_period_sessions(conn, range.start, range.end, topics)? {
+        let Some(column) = days.iter().position(|d| *d == day) else {
+            continue;
+        };
+        let row = match rows.iter().position(|(t, _)| *t == topic) {
+            Some(i) => &mut rows[i].1,
+            None => {
+                rows.push((topic, vec![0.0; days.len()]));
+                &mut rows.last_mut().unwrap().1
+            }
+        };
+        row[column] += hours;
+    }
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(Timesheet { label: range.label.clone(), days, rows })
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// One line per topic with decimal hours, days as ISO dates in the header.
+pub fn to_csv(sheet: &Timesheet) -> String {
+    let mut csv = String::from("topic");
+    for day in &sheet.days {
+        csv.push_str(&format!(",{}", day.format("%Y-%m-%d")));
+    }
+    csv.push_str(",total\n");
+
+    let line = |name: &str, hours: &[f64], total: f64| {
+        let cells: Vec<String> = hours.iter().map(|h| format!("{:.2}", h)).collect();
+        format!("{},{},{:.2}\n", csv_field(name), cells.join(","), total)
+    };
+    for (topic, hours) in &sheet.rows {
+        csv.push_str(&line(topic, hours, row_total(hours)));
+    }
+    csv.push_str(&line("total", &sheet.day_totals(), sheet.total()));
+
+    csv
+}
+
+/// Hours in a cell, empty for days without any.
+fn cell(hours: f64) -> String {
+    if hours > 0.0 {
+        settings::hours(hours)
+    } else {
+        String::new()
+    }
+}
+
+pub fn to_markdown(sheet: &Timesheet) -> String {
+    let mut md = format!("# Timesheet {}\n\n", sheet.label);
+
+    md.push_str("| Topic |");
+    for day in &sheet.days {
+        md.push_str(&format!(" {} |", sheet.heading(*day)));
+    }
+    md.push_str(" Total |\n|---|");
+    md.push_str(&"---:|".repeat(sheet.days.len() + 1));
+    md.push('\n');
+
+    for (topic, hours) in &sheet.rows {
+        md.push_str(&format!("| {} |", topic.replace('|', "\\|")));
+        for h in hours {
+            md.push_str(&format!(" {} |", cell(*h)));
+        }
+        md.push_str(&format!(" {} |\n", settings::hours(row_total(hours))));
+    }
+
+    md.push_str("| **Total** |");
+    for h in sheet.day_totals() {
+        md.push_str(&format!(" {} |", cell(h)));
+    }
+    md.push_str(&format!(" **{}** |\n", settings::hours(sheet.total())));
+
+    md
+}
@@ -0,0 +1,124 @@
+// Topics are paths like `acme/website/frontend`. These helpers split them into
+// levels for roll-up reports and match them against filter patterns.
+
+pub struct TopicNode {
+    pub name: String,
+    pub hours: f64,
+    pub children: Vec<TopicNode>,
+}
+
+fn segments<'a>(topic: &'a str, separator: &str) -> Vec<&'a str> {
+    if separator.is_empty() {
+        vec![topic]
+    } else {
+        topic.split(separator).collect()
+    }
+}
+
+/// Rolls flat per-topic totals up into a tree. Levels deeper than `depth` are
+/// folded into their parent. Siblings are sorted by hours, largest first.
+pub fn build_tree(topics: &[(String, f64)], separator: &str, depth: Option<usize>) -> Vec<TopicNode> {
+    let mut roots: Vec<TopicNode> = Vec::new();
+
+    for (topic, hours) in topics {
+        let mut level = &mut roots;
+        for segment in segments(topic, separator).into_iter().take(depth.unwrap_or(usize::MAX).max(1)) {
+            let index = match level.iter().position(|n| n.name == segment) {
+                Some(i) => i,
+                None => {
+                    level.push(TopicNode { name: segment.to_string(), hours: 0.0, children: Vec::new() });
+                    level.len() - 1
+                }
+            };
+            level[index].hours += hours;
+            level = &mut level[index].children;
+        }
+    }
+
+    sort_tree(&mut roots);
+    roots
+}
+
+fn sort_tree(nodes: &mut [TopicNode]) {
+    nodes.sort_by(|a, b| b.hours.partial_cmp(&a.hours).unwrap());
+    for node in nodes {
+        sort_tree(&mut node.children);
+    }
+}
+
+/// Matches a topic against a pattern. `*` and `?` match within one level,
+/// a `**` level matches any number of levels (including none), so `acme/**`
+/// selects `acme` and everything below it.
+pub fn matches(pattern: &str, topic: &str, separator: &str) -> bool {
+    match_levels(&segments(pattern, separator), &segments(topic, separator))
+}
+
+fn match_levels(pattern: &[&str], topic: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => topic.is_empty(),
+        Some((&"**", rest)) => (0..=topic.len()).any(|skip| match_levels(rest, &topic[skip..])),
+        Some((first, rest)) => match topic.split_first() {
+            Some((segment, topic_rest)) => match_glob(first, segment) && match_levels(rest, topic_rest),
+            None => false,
+        },
+    }
+}
+
+fn match_glob(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Classic two-pointer wildcard match with backtracking to the last `*`
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn double_star_matches_any_number_of_levels() {
+        assert!(matches("acme/**", "acme", "/"));
+        assert!(matches("acme/**", "acme/website", "/"));
+        assert!(matches("acme/**", "acme/website/frontend", "/"));
+        assert!(matches("**/frontend", "acme/website/frontend", "/"));
+        assert!(matches("acme/**/frontend", "acme/frontend", "/"));
+        assert!(!matches("acme/**", "acmecorp", "/"));
+        assert!(!matches("acme/**/frontend", "acme/website/backend", "/"));
+    }
+
+    #[test]
+    fn single_wildcards_stay_within_a_level() {
+        assert!(matches("acme/*", "acme/website", "/"));
+        assert!(matches("ac?e/web*", "acme/website", "/"));
+        assert!(!matches("acme/*", "acme", "/"));
+        assert!(!matches("acme/*", "acme/website/frontend", "/"));
+        assert!(!matches("acme*", "acme/website", "/"));
+    }
+
+    #[test]
+    fn uses_the_configured_separator() {
+        assert!(matches("acme:**", "acme:website:frontend", ":"));
+        assert!(!matches("acme/*", "acme:website", ":"));
+        assert!(matches("acme/*", "acme/website", ""));
+    }
+}
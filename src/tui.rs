@@ -0,0 +1,431 @@
+// Full screen view of the running and recent sessions. Every change goes
+// through the same queries and journal as the CLI, so undo works the same.
+
+use rusqlite::Connection;
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Local};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use std::time::Duration;
+use crate::queries::{self, Session};
+use crate::{display, journal, periods, settings, Period};
+
+const SESSION_LIMIT: usize = 500;
+const TICK: Duration = Duration::from_millis(250);
+
+enum Mode {
+    Normal,
+    Form(Form),
+    ConfirmDelete(i64),
+}
+
+enum FormAction {
+    Start,
+    /// Stop this session and start another topic
+    Switch(i64),
+    Edit(i64),
+}
+
+struct Form {
+    action: FormAction,
+    fields: Vec<(&'static str, String)>,
+    focus: usize,
+}
+
+impl Form {
+    fn title(&self) -> String {
+        match self.action {
+            FormAction::Start => "Start session".to_string(),
+            FormAction::Switch(id) => format!("Switch session {} to", id),
+            FormAction::Edit(id) => format!("Edit session {}", id),
+        }
+    }
+
+    fn value(&self, label: &str) -> &str {
+        self.fields.iter().find(|(l, _)| *l == label).map(|(_, v)| v.trim()).unwrap_or("")
+    }
+}
+
+struct App {
+    sessions: Vec<Session>,
+    active: Vec<Session>,
+    totals: Vec<(String, f64)>,
+    table: TableState,
+    mode: Mode,
+    message: Option<String>,
+    quit: bool,
+}
+
+pub fn run(conn: &Connection) -> Result<()> {
+    let mut app = App {
+        sessions: Vec::new(),
+        active: Vec::new(),
+        totals: Vec::new(),
+        table: TableState::default().with_selected(Some(0)),
+        mode: Mode::Normal,
+        message: None,
+        quit: false,
+    };
+    app.reload(conn)?;
+
+    let mut terminal = ratatui::init();
+    let result = app.event_loop(conn, &mut terminal);
+    ratatui::restore();
+    result
+}
+
+fn hours_between(start: &DateTime<FixedOffset>, end: &DateTime<FixedOffset>) -> f64 {
+    end.signed_duration_since(*start).num_seconds() as f64 / 3600.0
+}
+
+impl App {
+    fn event_loop(&mut self, conn: &Connection, terminal: &mut DefaultTerminal) -> Result<()> {
+        let mut since_reload = Duration::ZERO;
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if event::poll(TICK)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press {
+                        self.handle_key(conn, key)?;
+                    }
+                }
+            } else {
+                since_reload += TICK;
+            }
+
+            // Pick up changes made from other terminals
+            if since_reload >= Duration::from_secs(5) {
+                self.reload(conn)?;
+                since_reload = Duration::ZERO;
+            }
+        }
+        Ok(())
+    }
+
+    fn reload(&mut self, conn: &Connection) -> Result<()> {
+        let selected_id = self.selected().map(|s| s.id);
+
+        self.sessions = queries::get_sessions(conn, SESSION_LIMIT, &queries::TopicFilter::default())?;
+        self.active = queries::get_active_sessions(conn)?;
+
+        let now = Local::now();
+        self.totals.clear();
+        for period in [Period::Day, Period::Week, Period::Month] {
+            let range = periods::range(period, 0, now.naive_local());
+            let total = queries::get_period_total(conn, range.start, range.end, now)?;
+            self.totals.push((range.label, total));
+        }
+
+        let index = selected_id
+            .and_then(|id| self.sessions.iter().position(|s| s.id == id))
+            .or(self.table.selected())
+            .map(|i| i.min(self.sessions.len().saturating_sub(1)));
+        self.table.select(index);
+        Ok(())
+    }
+
+    fn selected(&self) -> Option<&Session> {
+        self.table.selected().and_then(|i| self.sessions.get(i))
+    }
+
+    /// The session stop and switch act on: the selected one if it is
+    /// running, otherwise the only running session.
+    fn stop_target(&self) -> Option<&Session> {
+        match self.selected() {
+            Some(s) if s.end.is_none() => Some(s),
+            _ if self.active.len() == 1 => self.active.first(),
+            _ => None,
+        }
+    }
+
+    fn handle_key(&mut self, conn: &Connection, key: KeyEvent) -> Result<()> {
+        let mode = std::mem::replace(&mut self.mode, Mode::Normal);
+        match mode {
+            Mode::Normal => self.handle_normal_key(conn, key)?,
+            Mode::Form(form) => self.handle_form_key(conn, form, key)?,
+            Mode::ConfirmDelete(id) => {
+                if let KeyCode::Char('y') = key.code {
+                    self.perform(conn, |tx| delete(tx, id));
+                } else {
+                    self.message = Some("Delete cancelled".to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_normal_key(&mut self, conn: &Connection, key: KeyEvent) -> Result<()> {
+        self.message = None;
+        let last = self.sessions.len().saturating_sub(1);
+        let selected = self.table.selected().unwrap_or(0);
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Down | KeyCode::Char('j') => self.table.select(Some((selected + 1).min(last))),
+            KeyCode::Up | KeyCode::Char('k') => self.table.select(Some(selected.saturating_sub(1))),
+            KeyCode::PageDown => self.table.select(Some((selected + 10).min(last))),
+            KeyCode::PageUp => self.table.select(Some(selected.saturating_sub(10))),
+            KeyCode::Home | KeyCode::Char('g') => self.table.select(Some(0)),
+            KeyCode::End | KeyCode::Char('G') => self.table.select(Some(last)),
+            KeyCode::Char('s') => {
+                self.mode = Mode::Form(Form { action: FormAction::Start, fields: vec![("Topic", String::new())], focus: 0 });
+            }
+            KeyCode::Char('x') => match self.stop_target().map(|s| (s.id, s.topic.clone())) {
+                Some((id, topic)) => self.perform(conn, |tx| {
+                    journal::transaction(tx, &format!("stop {}", topic), |tx| queries::stop_session(tx, id))?;
+                    Ok(format!("Stopped {}", topic))
+                }),
+                None => self.message = Some("Select the running session to stop".to_string()),
+            },
+            KeyCode::Char('w') => match self.stop_target() {
+                Some(s) => {
+                    self.mode = Mode::Form(Form { action: FormAction::Switch(s.id), fields: vec![("Topic", String::new())], focus: 0 });
+                }
+                None => self.message = Some("Select the running session to switch from".to_string()),
+            },
+            KeyCode::Char('e') => {
+                if let Some(s) = self.selected() {
+                    let fields = vec![
+                        ("Topic", s.topic.clone()),
+                        ("Start", s.start.format("%d.%m.%Y %H:%M").to_string()),
+                        ("End", s.end.map(|e| e.format("%d.%m.%Y %H:%M").to_string()).unwrap_or_default()),
+                    ];
+                    self.mode = Mode::Form(Form { action: FormAction::Edit(s.id), fields, focus: 0 });
+                }
+            }
+            KeyCode::Char('d') => {
+                if let Some(s) = self.selected() {
+                    self.mode = Mode::ConfirmDelete(s.id);
+                }
+            }
+            KeyCode::Char('u') => self.perform(conn, |tx| {
+                Ok(journal::undo(tx)?.map(|d| format!("Undone: {}", d)).unwrap_or_else(|| "Nothing to undo".to_string()))
+            }),
+            KeyCode::Char('r') => self.perform(conn, |tx| {
+                Ok(journal::redo(tx)?.map(|d| format!("Redone: {}", d)).unwrap_or_else(|| "Nothing to redo".to_string()))
+            }),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handle_form_key(&mut self, conn: &Connection, mut form: Form, key: KeyEvent) -> Result<()> {
+        match key.code {
+            KeyCode::Esc => return Ok(()),
+            KeyCode::Tab | KeyCode::Down => form.focus = (form.focus + 1) % form.fields.len(),
+            KeyCode::BackTab | KeyCode::Up => form.focus = (form.focus + form.fields.len() - 1) % form.fields.len(),
+            KeyCode::Backspace => {
+                form.fields[form.focus].1.pop();
+            }
+            KeyCode::Char(c) => form.fields[form.focus].1.push(c),
+            KeyCode::Enter => {
+                let result = submit(conn, &form);
+                match result {
+                    Ok(message) => {
+                        self.message = Some(message);
+                        self.reload(conn)?;
+                    }
+                    Err(e) => {
+                        // Keep the form open so the input can be fixed
+                        self.message = Some(e.to_string());
+                        self.mode = Mode::Form(form);
+                    }
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        self.mode = Mode::Form(form);
+        Ok(())
+    }
+
+    /// Runs a change and reports its outcome in the status line.
+    fn perform(&mut self, conn: &Connection, f: impl FnOnce(&Connection) -> Result<String>) {
+        self.message = Some(match f(conn) {
+            Ok(message) => message,
+            Err(e) => e.to_string(),
+        });
+        if let Err(e) = self.reload(conn) {
+            self.message = Some(e.to_string());
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let top_height = (self.active.len().max(1) as u16 + 2).max(self.totals.len() as u16 + 2);
+        let [top, list, footer] = Layout::vertical([
+            Constraint::Length(top_height),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ]).areas(frame.area());
+        let [active_area, totals_area] = Layout::horizontal([Constraint::Min(30), Constraint::Length(44)]).areas(top);
+
+        self.draw_active(frame, active_area);
+        self.draw_totals(frame, totals_area);
+        self.draw_sessions(frame, list);
+        self.draw_footer(frame, footer);
+
+        match &self.mode {
+            Mode::Form(form) => draw_form(frame, form),
+            Mode::ConfirmDelete(id) => {
+                let area = popup(frame.area(), 40, 3);
+                frame.render_widget(Clear, area);
+                frame.render_widget(
+                    Paragraph::new(format!(" Delete session {}? (y/n)", id)).block(Block::default().borders(Borders::ALL)),
+                    area,
+                );
+            }
+            Mode::Normal => {}
+        }
+    }
+
+    fn draw_active(&self, frame: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = if self.active.is_empty() {
+            vec![Line::styled(" Nothing running", Style::default().fg(Color::DarkGray))]
+        } else {
+            self.active.iter().map(|s| Line::from(vec![
+                Span::styled(format!(" {:>9} ", display::format_elapsed(&s.start)), Style::default().fg(Color::Green).add_modifier(Modifier::BOLD)),
+                Span::raw(format!(" {}  since {}", s.topic, s.start.format("%d.%m. %H:%M"))),
+            ])).collect()
+        };
+        frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Running ")), area);
+    }
+
+    fn draw_totals(&self, frame: &mut Frame, area: Rect) {
+        let lines: Vec<Line> = self.totals.iter()
+            .map(|(label, hours)| Line::raw(format!(" {:<32} {:>7}", label, settings::hours(*hours))))
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Totals ")), area);
+    }
+
+    fn draw_sessions(&mut self, frame: &mut Frame, area: Rect) {
+        let now = Local::now().fixed_offset();
+        let rows = self.sessions.iter().map(|s| {
+            let (end, hours) = match s.end {
+                Some(end) => (settings::datetime(&end), settings::hours(hours_between(&s.start, &end))),
+                None => ("ACTIVE".to_string(), settings::hours(hours_between(&s.start, &now))),
+            };
+            let style = match (s.end, s.locked) {
+                (None, _) => Style::default().fg(Color::Green),
+                (_, true) => Style::default().fg(Color::DarkGray),
+                _ => Style::default(),
+            };
+            Row::new(vec![
+                s.id.to_string(),
+                s.topic.clone(),
+                settings::datetime(&s.start),
+                end,
+                hours,
+                if s.locked { "locked".to_string() } else { String::new() },
+            ]).style(style)
+        });
+
+        let table = Table::new(rows, [
+            Constraint::Length(6),
+            Constraint::Min(20),
+            Constraint::Length(17),
+            Constraint::Length(17),
+            Constraint::Length(8),
+            Constraint::Length(6),
+        ])
+            .header(Row::new(["ID", "Topic", "Start", "End", "Hours", ""]).style(Style::default().add_modifier(Modifier::BOLD)))
+            .block(Block::default().borders(Borders::ALL).title(" Sessions "))
+            .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        frame.render_stateful_widget(table, area, &mut self.table);
+    }
+
+    fn draw_footer(&self, frame: &mut Frame, area: Rect) {
+        let text = match (&self.mode, &self.message) {
+            (Mode::Form(_), Some(message)) => format!(" {}", message),
+            (Mode::Form(_), None) => " Enter save  Tab next field  Esc cancel".to_string(),
+            (_, Some(message)) => format!(" {}", message),
+            (_, None) => " s start  x stop  w switch  e edit  d delete  u undo  r redo  q quit".to_string(),
+        };
+        frame.render_widget(Paragraph::new(text).style(Style::default().fg(Color::DarkGray)), area);
+    }
+}
+
+fn draw_form(frame: &mut Frame, form: &Form) {
+    let area = popup(frame.area(), 50, form.fields.len() as u16 + 2);
+    let lines: Vec<Line> = form.fields.iter().enumerate().map(|(i, (label, value))| {
+        let style = if i == form.focus { Style::default().add_modifier(Modifier::BOLD) } else { Style::default() };
+        let cursor = if i == form.focus { "▏" } else { "" };
+        Line::styled(format!(" {:<6} {}{}", label, value, cursor), style)
+    }).collect();
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(format!(" {} ", form.title()))),
+        area,
+    );
+}
+
+fn popup(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+fn submit(conn: &Connection, form: &Form) -> Result<String> {
+    let topic = form.value("Topic");
+    if topic.is_empty() {
+        anyhow::bail!("Topic can't be empty");
+    }
+
+    match form.action {
+        FormAction::Start => {
+            if queries::get_active_session_for_topic(conn, topic)?.is_some() {
+                anyhow::bail!("Session for '{}' is already active", topic);
+            }
+            journal::transaction(conn, &format!("start {}", topic), |tx| queries::start_session(tx, topic))?;
+            Ok(format!("Started {}", topic))
+        }
+        FormAction::Switch(id) => {
+            if queries::get_active_session_for_topic(conn, topic)?.is_some() {
+                anyhow::bail!("Session for '{}' is already active", topic);
+            }
+            journal::transaction(conn, &format!("switch to {}", topic), |tx| {
+                queries::stop_session(tx, id)?;
+                queries::start_session(tx, topic)
+            })?;
+            Ok(format!("Switched to {}", topic))
+        }
+        FormAction::Edit(id) => {
+            let start = queries::parse_datetime(form.value("Start"))?;
+            let end = match form.value("End") {
+                "" => None,
+                end => Some(queries::parse_datetime(end)?),
+            };
+            if let Some(end) = &end {
+                if DateTime::parse_from_rfc3339(end)? <= DateTime::parse_from_rfc3339(&start)? {
+                    anyhow::bail!("End time must be after start time");
+                }
+            }
+
+            journal::transaction(conn, &format!("edit session {}", id), |tx| {
+                queries::update_session_topic(tx, id, topic)?;
+                queries::update_session_start(tx, id, &start)?;
+                if let Some(end) = &end {
+                    queries::update_session_end(tx, id, end)?;
+                }
+                Ok(())
+            })?;
+            Ok(format!("Updated session {}", id))
+        }
+    }
+}
+
+fn delete(conn: &Connection, id: i64) -> Result<String> {
+    journal::transaction(conn, &format!("delete session {}", id), |tx| queries::delete_session(tx, id))?;
+    Ok(format!("Moved session {} to trash", id))
+}